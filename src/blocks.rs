@@ -0,0 +1,140 @@
+use image::{Rgba, RgbaImage};
+use std::env;
+use std::fmt::Write as _;
+
+// Each character cell shows two vertically stacked pixels: '▀' with the
+// foreground colour as the top pixel and the background colour as the bottom one.
+
+/// Pixels with alpha below this show the terminal background
+const ALPHA_THRESHOLD: u8 = 128;
+
+/// Foreground and background of one cell (None = terminal default)
+type CellColors = (Option<Rgba<u8>>, Option<Rgba<u8>>);
+
+/// Render an image as half-block glyphs, one cell per pixel column and two pixel rows
+///
+/// Works in any terminal with colour support. The caller sizes the image to the
+/// slot's width in cells and twice its rows (see `Renderer::pixel_size`), so the layout matches.
+pub fn render(rgba: &RgbaImage) -> String {
+    let (width, rows) = (rgba.width(), rgba.height() / 2);
+    let truecolor = truecolor_supported();
+
+    // Only emit SGR codes when a colour changes from the previous cell
+    let mut out = String::new();
+    for row in 0..rows {
+        let mut last: Option<CellColors> = None;
+        for x in 0..width {
+            let top = *rgba.get_pixel(x, row * 2);
+            let bottom = *rgba.get_pixel(x, row * 2 + 1);
+            let (fg, bg, glyph) = match (top[3] >= ALPHA_THRESHOLD, bottom[3] >= ALPHA_THRESHOLD) {
+                (true, true) => (Some(top), Some(bottom), '▀'),
+                (true, false) => (Some(top), None, '▀'),
+                (false, true) => (Some(bottom), None, '▄'),
+                (false, false) => (None, None, ' '),
+            };
+            if last != Some((fg, bg)) {
+                match fg {
+                    Some(color) => push_color(&mut out, 38, &color, truecolor),
+                    None => out.push_str("\x1b[39m"),
+                }
+                match bg {
+                    Some(color) => push_color(&mut out, 48, &color, truecolor),
+                    None => out.push_str("\x1b[49m"),
+                }
+                last = Some((fg, bg));
+            }
+            out.push(glyph);
+        }
+        if row + 1 < rows {
+            // Next row starts below this one's first cell, so the image can sit beside others
+            let _ = write!(out, "\x1b[0m\x1b[B\x1b[{}D", width);
+        } else {
+            out.push_str("\x1b[0m\n");
+        }
+    }
+    out
+}
+
+/// 24-bit colour is advertised via COLORTERM; otherwise use the 256-colour palette
+fn truecolor_supported() -> bool {
+    env::var("COLORTERM").is_ok_and(|v| v == "truecolor" || v == "24bit")
+}
+
+/// Append an SGR colour: `layer` is 38 (foreground) or 48 (background)
+fn push_color(out: &mut String, layer: u8, pixel: &Rgba<u8>, truecolor: bool) {
+    let [r, g, b, _] = pixel.0;
+    if truecolor {
+        let _ = write!(out, "\x1b[{};2;{};{};{}m", layer, r, g, b);
+    } else {
+        let _ = write!(out, "\x1b[{};5;{}m", layer, xterm_256_index(r, g, b));
+    }
+}
+
+/// Nearest colour in the xterm 256-colour palette (6×6×6 cube or grey ramp)
+fn xterm_256_index(r: u8, g: u8, b: u8) -> u8 {
+    const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let cube_step = |c: u8| -> u8 {
+        match c {
+            0..=47 => 0,
+            48..=114 => 1,
+            _ => (c - 35) / 40,
+        }
+    };
+    let (cr, cg, cb) = (cube_step(r), cube_step(g), cube_step(b));
+    let cube_color = (CUBE_LEVELS[cr as usize], CUBE_LEVELS[cg as usize], CUBE_LEVELS[cb as usize]);
+
+    // Grey ramp 232..=255 covers 8, 18, ..., 238
+    let avg = (r as u32 + g as u32 + b as u32) / 3;
+    let grey_step = if avg > 238 { 23 } else { avg.saturating_sub(3) / 10 } as u8;
+    let grey = 8 + grey_step * 10;
+
+    let dist = |(r2, g2, b2): (u8, u8, u8)| {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(r, r2) + d(g, g2) + d(b, b2)
+    };
+    if dist((grey, grey, grey)) < dist(cube_color) {
+        232 + grey_step
+    } else {
+        16 + 36 * cr + 6 * cg + cb
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn black_and_white_are_cube_corners() {
+        assert_eq!(xterm_256_index(0, 0, 0), 16);
+        assert_eq!(xterm_256_index(255, 255, 255), 231);
+    }
+
+    #[test]
+    fn greys_between_cube_levels_use_the_ramp() {
+        assert_eq!(xterm_256_index(8, 8, 8), 232);
+        assert_eq!(xterm_256_index(128, 128, 128), 244);
+        assert_eq!(xterm_256_index(238, 238, 238), 255);
+    }
+
+    #[test]
+    fn greys_on_a_cube_level_stay_in_the_cube() {
+        assert_eq!(xterm_256_index(95, 95, 95), 59);
+    }
+
+    #[test]
+    fn primaries_and_secondaries_map_to_cube_corners() {
+        assert_eq!(xterm_256_index(255, 0, 0), 196);
+        assert_eq!(xterm_256_index(0, 255, 0), 46);
+        assert_eq!(xterm_256_index(0, 0, 255), 21);
+        assert_eq!(xterm_256_index(255, 255, 0), 226);
+        assert_eq!(xterm_256_index(0, 255, 255), 51);
+        assert_eq!(xterm_256_index(255, 0, 255), 201);
+    }
+
+    #[test]
+    fn channels_snap_to_the_nearest_cube_level() {
+        // 47 is closer to 0 than 95, 48 closer to 95
+        assert_eq!(xterm_256_index(47, 255, 0), 46);
+        assert_eq!(xterm_256_index(48, 255, 0), 82);
+    }
+}
@@ -0,0 +1,161 @@
+use image::{DynamicImage, GenericImageView};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::SystemTime;
+
+use crate::formats;
+use crate::metadata::Orientation;
+use crate::render::{Payload, Renderer};
+
+// Decoded images are kept across draws so Ctrl+L, resizes and undo only redo the
+// cheap part. Entries are keyed by path and mtime (an edited file is decoded afresh)
+// and evicted least-recently-used once their pixels pass CACHE_LIMIT_BYTES.
+// The encoded payload for the last size drawn is kept with each entry.
+
+/// Decoded pixels kept in memory; the payloads come on top (they're far smaller)
+const CACHE_LIMIT_BYTES: usize = 256 * 1024 * 1024;
+
+/// Images larger than this on either side are shrunk right after decoding, so a cached
+/// image holds at most 4000×4000 pixels (64 MB as RGBA) however large the file. This is
+/// only a memory bound: payloads are resampled again to their slot's pixel box.
+const MAX_DIMENSION: u32 = 4000;
+
+static CACHE: Mutex<Cache> = Mutex::new(Cache { entries: Vec::new(), bytes: 0 });
+
+struct Cache {
+    /// Least recently used first
+    entries: Vec<Entry>,
+    bytes: usize,
+}
+
+struct Entry {
+    path: PathBuf,
+    mtime: SystemTime,
+    image: Arc<CachedImage>,
+}
+
+/// What a payload was encoded for: protocol, width in cells, cell size
+type PayloadKey = (Renderer, u32, (u32, u32));
+
+/// An image decoded once, plus the payloads encoded from it so far
+pub struct CachedImage {
+    /// Pixel size of the file, upright (EXIF orientation applied)
+    pub orig_dims: (u32, u32),
+    /// Decoded pixels, upright and shrunk if the file was over MAX_DIMENSION
+    pub image: DynamicImage,
+    /// Last payload encoded and what it was for
+    payload: Mutex<Option<(PayloadKey, Arc<Payload>)>>,
+}
+
+impl CachedImage {
+    fn decode(path: &Path) -> Result<CachedImage, String> {
+        let decoded = formats::open(path)?;
+        // Phone photos are often stored sideways with an EXIF tag saying how to turn them
+        let img = match decoded.upright {
+            true => decoded.image,
+            false => Orientation::of(path).apply(decoded.image),
+        };
+        let (w, h) = img.dimensions();
+
+        // Pre-shrink truly massive images (>4000px) to bound the memory the cache holds;
+        // the final resample to the layout's size happens per payload in `Renderer::display`
+        let image = if w > MAX_DIMENSION || h > MAX_DIMENSION {
+            let encode_scale = MAX_DIMENSION as f32 / w.max(h) as f32;
+            let scaled_w = ((w as f32 * encode_scale) as u32).max(1);
+            let scaled_h = ((h as f32 * encode_scale) as u32).max(1);
+            img.resize_exact(scaled_w, scaled_h, image::imageops::FilterType::Lanczos3)
+        } else {
+            img
+        };
+        Ok(CachedImage {
+            orig_dims: (w, h),
+            image,
+            payload: Mutex::new(None),
+        })
+    }
+
+    /// Payload for `key`, reusing the last one if it was encoded for the same key
+    pub fn payload(
+        &self,
+        key: PayloadKey,
+        encode: impl FnOnce(&DynamicImage) -> Result<Payload, String>,
+    ) -> Result<Arc<Payload>, String> {
+        // A panic mid-encode (on the prefetch worker) leaves the last payload intact
+        let mut payload = self.payload.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some((last_key, data)) = payload.as_ref()
+            && *last_key == key
+        {
+            return Ok(data.clone());
+        }
+        let data = Arc::new(encode(&self.image)?);
+        *payload = Some((key, data.clone()));
+        Ok(data)
+    }
+
+    fn bytes(&self) -> usize {
+        self.image.as_bytes().len()
+    }
+}
+
+/// Decode `path`, or return the cached copy if the file hasn't changed since
+pub fn load(path: &Path) -> Result<Arc<CachedImage>, String> {
+    let mtime = modified(path)?;
+    if let Some(image) = CACHE.lock().unwrap().get(path, mtime) {
+        return Ok(image);
+    }
+    // Decode without holding the lock so other threads can use the cache meanwhile
+    let image = Arc::new(CachedImage::decode(path)?);
+    CACHE.lock().unwrap().insert(path, mtime, image.clone());
+    Ok(image)
+}
+
+/// Upright pixel size of an image: from the cache if decoded, else from the file's headers only
+pub fn dimensions(path: &Path) -> Result<(u32, u32), String> {
+    if let Ok(mtime) = modified(path)
+        && let Some(image) = CACHE.lock().unwrap().get(path, mtime)
+    {
+        return Ok(image.orig_dims);
+    }
+    match formats::dimensions(path)? {
+        (dims, true) => Ok(dims),
+        (dims, false) => Ok(Orientation::of(path).apply_to_dims(dims)),
+    }
+}
+
+fn modified(path: &Path) -> Result<SystemTime, String> {
+    fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .map_err(|e| e.to_string())
+}
+
+impl Cache {
+    /// Look up an entry and mark it most recently used
+    fn get(&mut self, path: &Path, mtime: SystemTime) -> Option<Arc<CachedImage>> {
+        let pos = self.entries.iter().position(|e| e.path == path && e.mtime == mtime)?;
+        let entry = self.entries.remove(pos);
+        let image = entry.image.clone();
+        self.entries.push(entry);
+        Some(image)
+    }
+
+    /// Add an entry (replacing older versions of the file), then evict down to the limit
+    fn insert(&mut self, path: &Path, mtime: SystemTime, image: Arc<CachedImage>) {
+        let mut freed = 0;
+        self.entries.retain(|e| {
+            let stale = e.path == path;
+            if stale {
+                freed += e.image.bytes();
+            }
+            !stale
+        });
+        self.bytes = self.bytes - freed + image.bytes();
+        self.entries.push(Entry { path: path.to_path_buf(), mtime, image });
+
+        // The newest entry always stays, even if it alone is over the limit
+        while self.bytes > CACHE_LIMIT_BYTES && self.entries.len() > 1 {
+            let evicted = self.entries.remove(0);
+            self.bytes -= evicted.image.bytes();
+        }
+    }
+}
@@ -0,0 +1,296 @@
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::walk::collision_name;
+
+// Number keys 1-9 file the current image into a directory.
+// Bindings come from `$XDG_CONFIG_HOME/piccy-picky/destinations.conf`, one per line:
+//   <key> <move|copy> <directory>      e.g.  1 move ~/Pictures/Family
+// and from --move-to/--copy-to on the command line, which override the file per key.
+
+const CONFIG_FILE: &str = "destinations.conf";
+
+/// Give up looking for a free name after this many collisions
+const MAX_COLLISIONS: u32 = 10_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Move,
+    Copy,
+}
+
+impl Mode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Move => "move",
+            Mode::Copy => "copy",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Destination {
+    pub dir: PathBuf,
+    pub mode: Mode,
+}
+
+/// Bindings for keys 1-9
+#[derive(Default)]
+pub struct Destinations {
+    slots: [Option<Destination>; 9],
+}
+
+impl Destinations {
+    /// Bindings from the config file, if there is one
+    pub fn load_config() -> Result<Destinations, String> {
+        let mut destinations = Destinations::default();
+        let Some(path) = config_path() else {
+            return Ok(destinations);
+        };
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(destinations),
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+        for (n, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, dest) = parse_binding(line).map_err(|e| format!("{}:{}: {}", path.display(), n + 1, e))?;
+            destinations.slots[key as usize - 1] = Some(dest);
+        }
+        Ok(destinations)
+    }
+
+    /// Apply a `--move-to`/`--copy-to` value: `N=DIR`, or just `DIR` for the first free key
+    pub fn bind_arg(&mut self, arg: &str, mode: Mode) -> Result<(), String> {
+        let (key, dir) = match arg.split_once('=') {
+            Some((key, dir)) if parse_key(key).is_some() => (parse_key(key), dir),
+            _ => (None, arg),
+        };
+        if dir.trim().is_empty() {
+            return Err("expected [N=]DIR with a non-empty directory".to_string());
+        }
+        let key = match key {
+            Some(key) => key,
+            None => match self.slots.iter().position(Option::is_none) {
+                Some(free) => free as u8 + 1,
+                None => return Err("all keys 1-9 are already bound".to_string()),
+            },
+        };
+        self.slots[key as usize - 1] = Some(Destination { dir: expand_home(dir), mode });
+        Ok(())
+    }
+
+    /// Destination bound to key 1-9
+    pub fn get(&self, key: u8) -> Option<&Destination> {
+        self.slots.get((key as usize).checked_sub(1)?)?.as_ref()
+    }
+
+    /// Bound keys in order
+    pub fn iter(&self) -> impl Iterator<Item = (u8, &Destination)> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(i, slot)| Some((i as u8 + 1, slot.as_ref()?)))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.iter().all(Option::is_none)
+    }
+}
+
+/// Move or copy `path` into `dest.dir`, returning the new file's path
+///
+/// The directory is created if needed. An existing file is never overwritten:
+/// the name gets a counter instead ("photo.2.jpg").
+pub fn file_into(path: &Path, dest: &Destination) -> io::Result<PathBuf> {
+    fs::create_dir_all(&dest.dir)?;
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?
+        .to_string_lossy()
+        .into_owned();
+
+    // Reserve a free name by creating it, then fill it in
+    for n in 1..=MAX_COLLISIONS {
+        let target = dest.dir.join(collision_name(&file_name, n));
+        let placeholder = match OpenOptions::new().write(true).create_new(true).open(&target) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        };
+        let result = match dest.mode {
+            // rename() atomically replaces our empty placeholder
+            Mode::Move => fs::rename(path, &target).or_else(|e| {
+                if e.raw_os_error() == Some(libc::EXDEV) {
+                    // Another filesystem: copy, then remove the original
+                    copy_contents(path, placeholder).and_then(|()| fs::remove_file(path))
+                } else {
+                    Err(e)
+                }
+            }),
+            Mode::Copy => copy_contents(path, placeholder),
+        };
+        if let Err(e) = result {
+            let _ = fs::remove_file(&target);
+            return Err(e);
+        }
+        return Ok(target);
+    }
+    Err(io::Error::new(io::ErrorKind::AlreadyExists, "no free file name"))
+}
+
+/// Reverse `file_into`: move the file back, or delete the copy
+///
+/// Refuses to overwrite if something now exists at the original path.
+pub fn unfile(original: &Path, filed: &Path, mode: Mode) -> io::Result<()> {
+    match mode {
+        Mode::Copy => fs::remove_file(filed),
+        Mode::Move => {
+            if original.symlink_metadata().is_ok() {
+                return Err(io::Error::new(io::ErrorKind::AlreadyExists, "original path is taken"));
+            }
+            fs::rename(filed, original).or_else(|e| {
+                if e.raw_os_error() == Some(libc::EXDEV) {
+                    let file = OpenOptions::new().write(true).create_new(true).open(original)?;
+                    copy_contents(filed, file).and_then(|()| fs::remove_file(filed))
+                } else {
+                    Err(e)
+                }
+            })
+        }
+    }
+}
+
+/// Copy file data into an already-open file, keeping the source's mtime
+pub fn copy_contents(from: &Path, mut to: File) -> io::Result<()> {
+    let mut source = File::open(from)?;
+    io::copy(&mut source, &mut to)?;
+    // Photo tools sort by date, so a copy shouldn't look brand new
+    if let Ok(modified) = source.metadata().and_then(|m| m.modified()) {
+        let _ = to.set_modified(modified);
+    }
+    to.sync_all()
+}
+
+/// One config line: `<key> <move|copy> <directory>`, any whitespace between the fields
+fn parse_binding(line: &str) -> Result<(u8, Destination), &'static str> {
+    const USAGE: &str = "expected <key> <move|copy> <directory>";
+    let (key, rest) = line.split_once(char::is_whitespace).ok_or(USAGE)?;
+    // The directory is the rest of the line, so it may contain spaces
+    let (mode, dir) = rest.trim_start().split_once(char::is_whitespace).ok_or(USAGE)?;
+    let dir = dir.trim();
+    if dir.is_empty() {
+        return Err(USAGE);
+    }
+    let mode = match mode {
+        "move" => Mode::Move,
+        "copy" => Mode::Copy,
+        _ => return Err("mode must be move or copy"),
+    };
+    let key = parse_key(key).ok_or("key must be 1-9")?;
+    Ok((key, Destination { dir: expand_home(dir), mode }))
+}
+
+fn parse_key(key: &str) -> Option<u8> {
+    key.parse().ok().filter(|k| (1..=9).contains(k))
+}
+
+/// `~/x` is not expanded by the shell inside `N=~/x`, so do it here
+fn expand_home(dir: &str) -> PathBuf {
+    match (dir.strip_prefix("~/"), env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(dir),
+    }
+}
+
+/// `$XDG_CONFIG_HOME/piccy-picky/destinations.conf`, defaulting to `~/.config`
+fn config_path() -> Option<PathBuf> {
+    let config_home = match env::var_os("XDG_CONFIG_HOME").filter(|v| !v.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_home.join("piccy-picky").join(CONFIG_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bindings_allow_any_whitespace_between_fields() {
+        for line in ["1 move /photos/family", "1  move\t/photos/family", "1\t move   /photos/family  "] {
+            let (key, dest) = parse_binding(line).unwrap();
+            assert_eq!(key, 1);
+            assert_eq!(dest.mode, Mode::Move);
+            assert_eq!(dest.dir, PathBuf::from("/photos/family"));
+        }
+    }
+
+    #[test]
+    fn binding_directories_keep_inner_spaces() {
+        let (key, dest) = parse_binding("9 copy /photos/Summer  2024").unwrap();
+        assert_eq!((key, dest.mode), (9, Mode::Copy));
+        assert_eq!(dest.dir, PathBuf::from("/photos/Summer  2024"));
+    }
+
+    #[test]
+    fn bad_bindings_are_rejected() {
+        assert_eq!(parse_binding("1 move").err(), Some("expected <key> <move|copy> <directory>"));
+        assert_eq!(parse_binding("1 move   ").err(), Some("expected <key> <move|copy> <directory>"));
+        assert_eq!(parse_binding("1 link /x").err(), Some("mode must be move or copy"));
+        assert_eq!(parse_binding("0 move /x").err(), Some("key must be 1-9"));
+        assert_eq!(parse_binding("10 copy /x").err(), Some("key must be 1-9"));
+    }
+
+    #[test]
+    fn empty_argument_directories_are_rejected() {
+        let mut destinations = Destinations::default();
+        for arg in ["", " ", "3=", "3=  "] {
+            assert!(destinations.bind_arg(arg, Mode::Move).is_err(), "{:?}", arg);
+        }
+        assert!(destinations.is_empty());
+        destinations.bind_arg("3=/photos", Mode::Copy).unwrap();
+        assert_eq!(destinations.get(3).unwrap().dir, PathBuf::from("/photos"));
+    }
+
+    #[test]
+    fn copies_keep_the_modification_time() {
+        let root = std::env::temp_dir().join(format!("piccy-picky-mtime-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let (from, to) = (root.join("old.jpg"), root.join("copy.jpg"));
+        fs::write(&from, "pixels").unwrap();
+        let taken = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_500_000_000);
+        File::options().write(true).open(&from).unwrap().set_modified(taken).unwrap();
+
+        copy_contents(&from, File::create(&to).unwrap()).unwrap();
+        assert_eq!(fs::metadata(&to).unwrap().modified().unwrap(), taken);
+        assert_eq!(fs::read_to_string(&to).unwrap(), "pixels");
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn filing_never_overwrites_and_numbers_the_name() {
+        let root = std::env::temp_dir().join(format!("piccy-picky-test-{}", std::process::id()));
+        let (src, dest_dir) = (root.join("src"), root.join("dest"));
+        fs::create_dir_all(&src).unwrap();
+        fs::create_dir_all(&dest_dir).unwrap();
+        fs::write(dest_dir.join("photo.jpg"), "existing").unwrap();
+        fs::write(src.join("photo.jpg"), "new").unwrap();
+
+        let copy = Destination { dir: dest_dir.clone(), mode: Mode::Copy };
+        assert_eq!(file_into(&src.join("photo.jpg"), &copy).unwrap(), dest_dir.join("photo.2.jpg"));
+        let moved = Destination { dir: dest_dir.clone(), mode: Mode::Move };
+        assert_eq!(file_into(&src.join("photo.jpg"), &moved).unwrap(), dest_dir.join("photo.3.jpg"));
+
+        assert_eq!(fs::read_to_string(dest_dir.join("photo.jpg")).unwrap(), "existing");
+        assert_eq!(fs::read_to_string(dest_dir.join("photo.2.jpg")).unwrap(), "new");
+        assert_eq!(fs::read_to_string(dest_dir.join("photo.3.jpg")).unwrap(), "new");
+        assert!(!src.join("photo.jpg").exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,51 @@
+use std::env;
+
+use crate::render::Renderer;
+use crate::term;
+
+/// What the terminal reported about itself
+#[derive(Debug)]
+struct Probe {
+    /// Answered the kitty graphics query with OK
+    kitty: bool,
+    /// Listed attribute 4 (sixel graphics) in its primary device attributes
+    sixel: bool,
+}
+
+/// Pick the best image protocol the terminal supports (raw mode must be enabled)
+///
+/// Preference: kitty graphics, iTerm2 inline images, Sixel, then half blocks.
+/// Queried capabilities win over environment hints, which cover terminals that
+/// support a protocol without advertising it (iTerm2 has no query for inline images).
+pub fn detect_renderer() -> Renderer {
+    let probe = probe_terminal();
+    let term_program = env::var("TERM_PROGRAM").unwrap_or_default();
+    let term = env::var("TERM").unwrap_or_default();
+
+    if probe.kitty || term == "xterm-kitty" || env::var_os("KITTY_WINDOW_ID").is_some() {
+        return Renderer::Kitty;
+    }
+    if matches!(term_program.as_str(), "iTerm.app" | "WezTerm")
+        || env::var_os("ITERM_SESSION_ID").is_some()
+        || env::var_os("WEZTERM_EXECUTABLE").is_some()
+    {
+        return Renderer::Iterm2;
+    }
+    if probe.sixel || term.starts_with("foot") || term.starts_with("mlterm") {
+        return Renderer::Sixel;
+    }
+    // Half blocks draw on anything with colour
+    Renderer::Blocks
+}
+
+/// Send a kitty graphics query; kitty-capable terminals answer it before DA1
+fn probe_terminal() -> Probe {
+    // a=q: query only, 1×1 RGB pixel, id 31 so we recognise the reply
+    let response = term::query_terminal("\x1b_Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA\x1b\\");
+
+    let attrs = term::parse_device_attributes(&response).unwrap_or_default();
+    Probe {
+        kitty: String::from_utf8_lossy(&response).contains("\x1b_Gi=31;OK"),
+        sixel: attrs.contains(&4),
+    }
+}
@@ -0,0 +1,156 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::destinations::{Destination, Mode};
+use crate::term;
+
+// With --dry-run nothing is moved: trash and filing decisions are only collected, then
+// summarised at the end and optionally written out as a script or JSON to apply later.
+
+/// Command the generated script uses to trash each file
+#[cfg(target_os = "macos")]
+const TRASH_COMMAND: &str = "trash"; // /usr/bin/trash, macOS 15+
+#[cfg(not(target_os = "macos"))]
+const TRASH_COMMAND: &str = "gio trash --";
+
+/// Print what the session would have done
+pub fn print_summary(kept: usize, would_trash: &[PathBuf], would_file: &[(PathBuf, Destination)]) {
+    let total: u64 = would_trash.iter().map(|p| file_size(p)).sum();
+    println!("\n🧪 Dry run: nothing was moved");
+    println!("  Kept:              {}", kept);
+    println!("  Would be trashed:  {} ({})", would_trash.len(), term::format_bytes(total));
+    for path in would_trash {
+        println!("    {}", path.display());
+    }
+    if !would_file.is_empty() {
+        println!("  Would be filed:    {}", would_file.len());
+        for (path, dest) in would_file {
+            println!("    {} {} → {}", dest.mode.name(), path.display(), dest.dir.display());
+        }
+    }
+}
+
+/// Write the would-be actions as JSON (`.json`) or as a shell script (anything else)
+pub fn write_report(out: &Path, would_trash: &[PathBuf], would_file: &[(PathBuf, Destination)]) -> io::Result<()> {
+    let report = if out.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json")) {
+        json_report(would_trash, would_file)
+    } else {
+        script_report(would_trash, would_file)
+    };
+    fs::write(out, report)
+}
+
+/// `[{"action": "trash", "path": ..., "size": ...}, {"action": "move", ..., "destination": ...}]`
+fn json_report(would_trash: &[PathBuf], would_file: &[(PathBuf, Destination)]) -> String {
+    let mut entries = Vec::new();
+    for path in would_trash {
+        entries.push(format!(
+            "  {{\"action\": \"trash\", \"path\": {}, \"size\": {}}}",
+            json_string(&path.to_string_lossy()),
+            file_size(path)
+        ));
+    }
+    for (path, dest) in would_file {
+        entries.push(format!(
+            "  {{\"action\": \"{}\", \"path\": {}, \"destination\": {}, \"size\": {}}}",
+            dest.mode.name(),
+            json_string(&path.to_string_lossy()),
+            json_string(&dest.dir.to_string_lossy()),
+            file_size(path)
+        ));
+    }
+    if entries.is_empty() {
+        return "[]\n".to_string();
+    }
+    format!("[\n{}\n]\n", entries.join(",\n"))
+}
+
+/// Files into a directory like `destinations::file_into`: never overwrites, numbers the
+/// name instead ("photo.2.jpg"), so the script reproduces what a real run would do
+const FILE_INTO_FUNCTION: &str = r#"file_into() { # <mv|cp -p> <file> <dir>
+    mkdir -p "$3" || return
+    name=${2##*/}; stem=${name%.*}; ext=${name##*.}
+    target=$3/$name; n=2
+    while [ -e "$target" ] || [ -L "$target" ]; do
+        if [ -n "$stem" ] && [ "$stem" != "$name" ]; then target=$3/$stem.$n.$ext; else target=$3/$name.$n; fi
+        n=$((n + 1))
+    done
+    $1 -- "$2" "$target"
+}
+"#;
+
+/// POSIX shell script that performs each action
+fn script_report(would_trash: &[PathBuf], would_file: &[(PathBuf, Destination)]) -> String {
+    let mut out = String::from("#!/bin/sh\n# Actions recorded by piccy-picky --dry-run\n");
+    for path in would_trash {
+        let _ = writeln!(out, "{} {}", TRASH_COMMAND, shell_quote(&path.to_string_lossy()));
+    }
+    if would_file.is_empty() {
+        return out;
+    }
+    out.push_str(FILE_INTO_FUNCTION);
+    for (path, dest) in would_file {
+        // -p: keep the mtime on copies
+        let command = match dest.mode {
+            Mode::Move => "mv",
+            Mode::Copy => "'cp -p'",
+        };
+        let _ = writeln!(
+            out,
+            "file_into {} {} {}",
+            command,
+            shell_quote(&path.to_string_lossy()),
+            shell_quote(&dest.dir.to_string_lossy())
+        );
+    }
+    out
+}
+
+fn file_size(path: &Path) -> u64 {
+    fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+/// Single-quote for sh: `it's` becomes `'it'\''s'`
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shell_quote_wraps_and_escapes_single_quotes() {
+        assert_eq!(shell_quote("/photos/a b.jpg"), "'/photos/a b.jpg'");
+        assert_eq!(shell_quote("it's $HOME"), "'it'\\''s $HOME'");
+        assert_eq!(shell_quote(""), "''");
+    }
+
+    #[test]
+    fn json_string_escapes_quotes_backslashes_and_controls() {
+        assert_eq!(json_string("plain"), "\"plain\"");
+        assert_eq!(json_string("say \"hi\"\\"), "\"say \\\"hi\\\"\\\\\"");
+        assert_eq!(json_string("a\tb\nc\u{1}"), "\"a\\tb\\nc\\u0001\"");
+        assert_eq!(json_string("Café 🙂"), "\"Café 🙂\"");
+    }
+}
@@ -0,0 +1,204 @@
+use image::DynamicImage;
+use std::path::Path;
+
+// Which files are images is decided by extension, from the formats this build can decode.
+// The image crate's defaults cover the common ones; AVIF and HEIF need system libraries
+// (dav1d, libheif), so they're opt-in cargo features: cargo build --features avif,heif.
+// JPEG XL is decoded in pure Rust but is a large dependency, so it's opt-in too (jxl).
+
+/// An image format this build decodes
+pub struct Format {
+    pub name: &'static str,
+    /// Lowercase extensions that select it
+    pub extensions: &'static [&'static str],
+}
+
+/// Everything this build can decode
+pub const FORMATS: &[Format] = &[
+    Format { name: "JPEG", extensions: &["jpg", "jpeg"] },
+    Format { name: "PNG", extensions: &["png"] },
+    Format { name: "GIF", extensions: &["gif"] },
+    Format { name: "WebP", extensions: &["webp"] },
+    Format { name: "BMP", extensions: &["bmp"] },
+    Format { name: "TIFF", extensions: &["tif", "tiff"] },
+    Format { name: "ICO", extensions: &["ico"] },
+    Format { name: "TGA", extensions: &["tga"] },
+    #[cfg(feature = "avif")]
+    Format { name: "AVIF", extensions: &["avif"] },
+    #[cfg(feature = "heif")]
+    Format { name: "HEIF", extensions: &["heic", "heif"] },
+    #[cfg(feature = "jxl")]
+    Format { name: "JPEG XL", extensions: &["jxl"] },
+];
+
+/// The format `path`'s extension selects, if this build decodes it (case-insensitive)
+pub fn for_path(path: &Path) -> Option<&'static Format> {
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    FORMATS.iter().find(|format| format.extensions.contains(&ext.as_str()))
+}
+
+/// Format names for --help, e.g. "JPEG, PNG, GIF"
+pub fn names() -> String {
+    FORMATS.iter().map(|format| format.name).collect::<Vec<_>>().join(", ")
+}
+
+/// Pixels as decoded, and whether the decoder already turned them upright
+pub struct Decoded {
+    pub image: DynamicImage,
+    /// True if rotation/mirroring from the file was applied (HEIF, JPEG XL); otherwise EXIF
+    /// orientation still needs applying. AVIF's own irot/imir are not applied by its decoder.
+    pub upright: bool,
+}
+
+/// Decode a whole image
+pub fn open(path: &Path) -> Result<Decoded, String> {
+    #[cfg(feature = "heif")]
+    if has_format(path, "HEIF") {
+        return heif::open(path).map(|image| Decoded { image, upright: true });
+    }
+    #[cfg(feature = "jxl")]
+    if has_format(path, "JPEG XL") {
+        return jxl::open(path).map(|image| Decoded { image, upright: true });
+    }
+    let image = image::open(path).map_err(|e| e.to_string())?;
+    Ok(Decoded { image, upright: false })
+}
+
+/// Pixel size from the file's headers, and whether it is already the upright size
+pub fn dimensions(path: &Path) -> Result<((u32, u32), bool), String> {
+    #[cfg(feature = "heif")]
+    if has_format(path, "HEIF") {
+        return heif::dimensions(path).map(|dims| (dims, true));
+    }
+    #[cfg(feature = "jxl")]
+    if has_format(path, "JPEG XL") {
+        return jxl::dimensions(path).map(|dims| (dims, true));
+    }
+    let dims = image::image_dimensions(path).map_err(|e| e.to_string())?;
+    Ok((dims, false))
+}
+
+#[cfg(any(feature = "heif", feature = "jxl"))]
+fn has_format(path: &Path, name: &str) -> bool {
+    for_path(path).is_some_and(|format| format.name == name)
+}
+
+/// HEIF/HEIC (iPhone photos) through libheif
+#[cfg(feature = "heif")]
+mod heif {
+    use image::{DynamicImage, RgbImage, RgbaImage};
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+    use std::path::Path;
+
+    /// Decode the primary image; libheif applies the file's rotation and mirroring
+    pub fn open(path: &Path) -> Result<DynamicImage, String> {
+        let context = HeifContext::read_from_file(&path.to_string_lossy()).map_err(|e| e.to_string())?;
+        let handle = context.primary_image_handle().map_err(|e| e.to_string())?;
+        let alpha = handle.has_alpha_channel();
+        let chroma = if alpha { RgbChroma::Rgba } else { RgbChroma::Rgb };
+        let decoded = LibHeif::new()
+            .decode(&handle, ColorSpace::Rgb(chroma), None)
+            .map_err(|e| e.to_string())?;
+        let plane = decoded.planes().interleaved.ok_or("no interleaved RGB plane")?;
+
+        // Rows are padded to `stride` bytes
+        let row_bytes = plane.width as usize * if alpha { 4 } else { 3 };
+        let mut pixels = Vec::with_capacity(row_bytes * plane.height as usize);
+        for row in plane.data.chunks(plane.stride).take(plane.height as usize) {
+            pixels.extend_from_slice(&row[..row_bytes]);
+        }
+        let image = if alpha {
+            RgbaImage::from_raw(plane.width, plane.height, pixels).map(DynamicImage::ImageRgba8)
+        } else {
+            RgbImage::from_raw(plane.width, plane.height, pixels).map(DynamicImage::ImageRgb8)
+        };
+        image.ok_or_else(|| "decoded plane has an unexpected size".to_string())
+    }
+
+    /// Upright size of the primary image, without decoding it
+    pub fn dimensions(path: &Path) -> Result<(u32, u32), String> {
+        let context = HeifContext::read_from_file(&path.to_string_lossy()).map_err(|e| e.to_string())?;
+        let handle = context.primary_image_handle().map_err(|e| e.to_string())?;
+        Ok((handle.width(), handle.height()))
+    }
+}
+
+/// JPEG XL through jxl-oxide
+#[cfg(feature = "jxl")]
+mod jxl {
+    use image::{DynamicImage, GrayAlphaImage, GrayImage, RgbImage, RgbaImage};
+    use jxl_oxide::{InitializeResult, JxlImage, PixelFormat};
+    use std::fs::File;
+    use std::io::Read;
+    use std::path::Path;
+
+    /// Decode the first frame; jxl-oxide applies the orientation from the header
+    pub fn open(path: &Path) -> Result<DynamicImage, String> {
+        let image = JxlImage::builder().open(path).map_err(|e| e.to_string())?;
+        let render = image.render_frame(0).map_err(|e| e.to_string())?;
+        let mut stream = render.stream();
+        let (width, height) = (stream.width(), stream.height());
+        let mut pixels = vec![0u8; width as usize * height as usize * stream.channels() as usize];
+        stream.write_to_buffer(&mut pixels);
+        let image = match image.pixel_format() {
+            PixelFormat::Gray => GrayImage::from_raw(width, height, pixels).map(DynamicImage::ImageLuma8),
+            PixelFormat::Graya => GrayAlphaImage::from_raw(width, height, pixels).map(DynamicImage::ImageLumaA8),
+            PixelFormat::Rgb => RgbImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8),
+            PixelFormat::Rgba => RgbaImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgba8),
+            PixelFormat::Cmyk | PixelFormat::Cmyka => return Err("CMYK JPEG XL is not supported".to_string()),
+        };
+        image.ok_or_else(|| "decoded frame has an unexpected size".to_string())
+    }
+
+    /// Upright size from the header, reading only as much of the file as that needs
+    pub fn dimensions(path: &Path) -> Result<(u32, u32), String> {
+        let mut file = File::open(path).map_err(|e| e.to_string())?;
+        let mut uninit = JxlImage::builder().build_uninit();
+        // Bytes the parser did not consume yet are fed again with the next read
+        let mut buf = vec![0u8; 4096];
+        let mut valid = 0;
+        loop {
+            let count = file.read(&mut buf[valid..]).map_err(|e| e.to_string())?;
+            if count == 0 {
+                return Err("file ends before the image header".to_string());
+            }
+            valid += count;
+            let consumed = uninit.feed_bytes(&buf[..valid]).map_err(|e| e.to_string())?;
+            buf.copy_within(consumed..valid, 0);
+            valid -= consumed;
+            match uninit.try_init().map_err(|e| e.to_string())? {
+                InitializeResult::NeedMoreData(more) => uninit = more,
+                InitializeResult::Initialized(image) => return Ok((image.width(), image.height())),
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "jxl"))]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// A 240×135 RGB codestream, from jxl-oxide's documentation
+    const TINY_JXL: &[u8] = &[
+        0xff, 0x0a, 0x30, 0x54, 0x10, 0x09, 0x08, 0x06, 0x01, 0x00, 0x78, 0x00, 0x4b, 0x38, 0x41, 0x3c, 0xb6, 0x3a, 0x51,
+        0xfe, 0x00, 0x47, 0x1e, 0xa0, 0x85, 0xb8, 0x27, 0x1a, 0x48, 0x45, 0x84, 0x1b, 0x71, 0x4f, 0xa8, 0x3e, 0x8e, 0x30,
+        0x03, 0x92, 0x84, 0x01,
+    ];
+
+    #[test]
+    fn jpeg_xl_files_are_sized_from_the_header_and_decoded() {
+        let path = std::env::temp_dir().join(format!("piccy-picky-{}.JXL", std::process::id()));
+        fs::write(&path, TINY_JXL).unwrap();
+        assert_eq!(for_path(&path).map(|format| format.name), Some("JPEG XL"));
+        assert_eq!(dimensions(&path), Ok(((240, 135), true)));
+        let decoded = open(&path).unwrap();
+        assert!(decoded.upright);
+        assert_eq!((decoded.image.width(), decoded.image.height()), (240, 135));
+
+        // The size comes from the first few bytes; a file cut before them has none
+        fs::write(&path, &TINY_JXL[..3]).unwrap();
+        assert!(dimensions(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
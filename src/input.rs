@@ -0,0 +1,323 @@
+use std::io;
+
+use crate::term;
+
+/// How long to wait for the rest of an escape sequence before treating ESC as a key.
+/// Sequences from a single keypress arrive together; this only matters over slow links.
+const ESCAPE_TIMEOUT_MS: i32 = 50;
+
+/// A decoded keypress
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Key {
+    /// Printable character, including non-ASCII from any keyboard layout
+    Char(char),
+    /// Ctrl+letter, as the lowercase letter (Ctrl+L is `Ctrl('l')`)
+    Ctrl(char),
+    /// Alt (Meta) + character, sent as ESC followed by the character
+    Alt(char),
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    Delete,
+    /// Function key F1..F12
+    F(u8),
+    Enter,
+    Tab,
+    BackTab,
+    Backspace,
+    Esc,
+    /// Special key with xterm-style modifiers, e.g. Ctrl+Right is `CSI 1;5C`
+    Modified { key: Box<Key>, shift: bool, alt: bool, ctrl: bool },
+    /// Text pasted while bracketed paste mode is on
+    Paste(String),
+    /// Escape sequence we don't understand
+    Unknown,
+}
+
+/// Where keypress bytes come from: the terminal, or a script in tests
+trait ByteSource {
+    /// Block for the next byte; `ErrorKind::Interrupted` if a signal arrives while waiting
+    fn next_byte(&mut self) -> io::Result<u8>;
+
+    /// The next byte if it arrives within ESCAPE_TIMEOUT_MS (rest of an escape sequence)
+    fn next_byte_soon(&mut self) -> Option<u8>;
+}
+
+/// Unbuffered stdin
+struct Terminal;
+
+impl ByteSource for Terminal {
+    fn next_byte(&mut self) -> io::Result<u8> {
+        term::read_byte()
+    }
+
+    fn next_byte_soon(&mut self) -> Option<u8> {
+        term::read_byte_timeout(ESCAPE_TIMEOUT_MS)
+    }
+}
+
+/// Block until a full keypress has been read and decode it
+///
+/// Returns `ErrorKind::Interrupted` if a signal (e.g. SIGWINCH) arrives while waiting.
+pub fn read_key() -> io::Result<Key> {
+    decode_key(&mut Terminal)
+}
+
+fn decode_key(source: &mut impl ByteSource) -> io::Result<Key> {
+    let byte = source.next_byte()?;
+    Ok(match byte {
+        0x1b => decode_escape(source),
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        0x7f | 0x08 => Key::Backspace,
+        0x01..=0x1a => Key::Ctrl((b'a' + byte - 1) as char),
+        0x00 => Key::Ctrl(' '),
+        _ => decode_utf8(source, byte).map(Key::Char).unwrap_or(Key::Unknown),
+    })
+}
+
+/// After ESC: CSI/SS3 sequence, Alt+key, or a lone Escape
+fn decode_escape(source: &mut impl ByteSource) -> Key {
+    match source.next_byte_soon() {
+        None => Key::Esc,
+        Some(b'[') => decode_csi(source),
+        Some(b'O') => match source.next_byte_soon() {
+            Some(final_byte) => decode_final(final_byte).unwrap_or(Key::Unknown),
+            None => Key::Alt('O'),
+        },
+        Some(0x1b) => Key::Esc,
+        Some(byte) => decode_utf8(source, byte).map(Key::Alt).unwrap_or(Key::Unknown),
+    }
+}
+
+/// `CSI params final`: arrows, Home/End, `~` keys, function keys, bracketed paste
+fn decode_csi(source: &mut impl ByteSource) -> Key {
+    let mut params = Vec::new();
+    let mut intermediates = false;
+    let final_byte = loop {
+        match source.next_byte_soon() {
+            Some(b @ 0x30..=0x3f) if !intermediates => params.push(b),
+            // No key sends intermediates, but the sequence still runs to its final byte
+            Some(0x20..=0x2f) => intermediates = true,
+            Some(b @ 0x40..=0x7e) => break b,
+            _ => return Key::Unknown,
+        }
+    };
+    if intermediates {
+        return Key::Unknown;
+    }
+
+    let params = String::from_utf8_lossy(&params);
+    let mut fields = params.split(';').map(|p| p.parse::<u32>().unwrap_or(1));
+    let first = fields.next().unwrap_or(1);
+    let modifier = fields.next().unwrap_or(1);
+
+    let key = if final_byte == b'~' {
+        match first {
+            200 => return Key::Paste(read_paste(source)),
+            1 | 7 => Key::Home,
+            2 => Key::Insert,
+            3 => Key::Delete,
+            4 | 8 => Key::End,
+            5 => Key::PageUp,
+            6 => Key::PageDown,
+            11..=15 => Key::F((first - 10) as u8),
+            17..=21 => Key::F((first - 11) as u8),
+            23 | 24 => Key::F((first - 12) as u8),
+            _ => Key::Unknown,
+        }
+    } else if final_byte == b'Z' {
+        Key::BackTab
+    } else {
+        match decode_final(final_byte) {
+            Some(key) => key,
+            None => return Key::Unknown,
+        }
+    };
+
+    with_modifiers(key, modifier)
+}
+
+/// Final byte shared by CSI and SS3 forms (`CSI A` / `SS3 A` are both Up)
+fn decode_final(byte: u8) -> Option<Key> {
+    Some(match byte {
+        b'A' => Key::Up,
+        b'B' => Key::Down,
+        b'C' => Key::Right,
+        b'D' => Key::Left,
+        b'H' => Key::Home,
+        b'F' => Key::End,
+        b'P' => Key::F(1),
+        b'Q' => Key::F(2),
+        b'R' => Key::F(3),
+        b'S' => Key::F(4),
+        _ => return None,
+    })
+}
+
+/// xterm modifier parameter: 1 + (shift=1 | alt=2 | ctrl=4)
+fn with_modifiers(key: Key, modifier: u32) -> Key {
+    let bits = modifier.saturating_sub(1);
+    if bits == 0 || key == Key::Unknown {
+        return key;
+    }
+    Key::Modified {
+        key: Box::new(key),
+        shift: bits & 1 != 0,
+        alt: bits & 2 != 0,
+        ctrl: bits & 4 != 0,
+    }
+}
+
+/// Collect pasted bytes up to the `CSI 201 ~` terminator
+///
+/// A resize mid-paste must not end it early, or the rest would be read as keys.
+fn read_paste(source: &mut impl ByteSource) -> String {
+    const END: &[u8] = b"\x1b[201~";
+    let mut bytes = Vec::new();
+    loop {
+        match source.next_byte() {
+            Ok(byte) => bytes.push(byte),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => break,
+        }
+        if bytes.ends_with(END) {
+            bytes.truncate(bytes.len() - END.len());
+            break;
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Decode a UTF-8 character whose first byte has already been read
+fn decode_utf8(source: &mut impl ByteSource, first: u8) -> Option<char> {
+    let len = match first {
+        0x00..=0x7f => return Some(first as char),
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => return None,
+    };
+    let mut buf = vec![first];
+    for _ in 1..len {
+        buf.push(source.next_byte_soon()?);
+    }
+    std::str::from_utf8(&buf).ok()?.chars().next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// Scripted input; `None` stands for a signal interrupting a blocking read
+    struct Script(VecDeque<Option<u8>>);
+
+    impl Script {
+        fn new(bytes: &[u8]) -> Script {
+            Script(bytes.iter().copied().map(Some).collect())
+        }
+    }
+
+    impl ByteSource for Script {
+        fn next_byte(&mut self) -> io::Result<u8> {
+            match self.0.pop_front() {
+                Some(Some(byte)) => Ok(byte),
+                Some(None) => Err(io::ErrorKind::Interrupted.into()),
+                None => Err(io::ErrorKind::UnexpectedEof.into()),
+            }
+        }
+
+        fn next_byte_soon(&mut self) -> Option<u8> {
+            self.0.pop_front().flatten()
+        }
+    }
+
+    /// Every key in `bytes`, until the input runs out
+    fn keys(bytes: &[u8]) -> Vec<Key> {
+        let mut script = Script::new(bytes);
+        std::iter::from_fn(|| decode_key(&mut script).ok()).collect()
+    }
+
+    fn modified(key: Key, shift: bool, alt: bool, ctrl: bool) -> Key {
+        Key::Modified { key: Box::new(key), shift, alt, ctrl }
+    }
+
+    #[test]
+    fn control_bytes() {
+        assert_eq!(keys(b"\r\n\t\x7f\x08"), [Key::Enter, Key::Enter, Key::Tab, Key::Backspace, Key::Backspace]);
+        assert_eq!(keys(b"\x0c\x00"), [Key::Ctrl('l'), Key::Ctrl(' ')]);
+    }
+
+    #[test]
+    fn arrows_in_csi_and_ss3_form() {
+        assert_eq!(keys(b"\x1b[A\x1b[B\x1b[C\x1b[D"), [Key::Up, Key::Down, Key::Right, Key::Left]);
+        assert_eq!(keys(b"\x1bOA\x1bOD"), [Key::Up, Key::Left]);
+    }
+
+    #[test]
+    fn xterm_modifiers() {
+        assert_eq!(keys(b"\x1b[1;5C"), [modified(Key::Right, false, false, true)]);
+        assert_eq!(keys(b"\x1b[1;2A"), [modified(Key::Up, true, false, false)]);
+        assert_eq!(keys(b"\x1b[3;8~"), [modified(Key::Delete, true, true, true)]);
+        assert_eq!(keys(b"\x1b[Z"), [Key::BackTab]);
+    }
+
+    #[test]
+    fn tilde_keys() {
+        assert_eq!(
+            keys(b"\x1b[1~\x1b[2~\x1b[3~\x1b[4~\x1b[5~\x1b[6~\x1b[H\x1b[F"),
+            [Key::Home, Key::Insert, Key::Delete, Key::End, Key::PageUp, Key::PageDown, Key::Home, Key::End]
+        );
+    }
+
+    #[test]
+    fn function_keys() {
+        assert_eq!(keys(b"\x1bOP\x1bOS"), [Key::F(1), Key::F(4)]);
+        assert_eq!(
+            keys(b"\x1b[11~\x1b[15~\x1b[17~\x1b[21~\x1b[23~\x1b[24~"),
+            [Key::F(1), Key::F(5), Key::F(6), Key::F(10), Key::F(11), Key::F(12)]
+        );
+        assert_eq!(keys(b"\x1b[15;2~"), [modified(Key::F(5), true, false, false)]);
+    }
+
+    #[test]
+    fn utf8_characters() {
+        assert_eq!(keys("é日🙂k".as_bytes()), [Key::Char('é'), Key::Char('日'), Key::Char('🙂'), Key::Char('k')]);
+        assert_eq!(keys(b"\xff"), [Key::Unknown]);
+    }
+
+    #[test]
+    fn alt_and_lone_escape() {
+        assert_eq!(keys(b"\x1bx"), [Key::Alt('x')]);
+        assert_eq!(keys("\x1bé".as_bytes()), [Key::Alt('é')]);
+        assert_eq!(keys(b"\x1b"), [Key::Esc]);
+        assert_eq!(keys(b"\x1b\x1b"), [Key::Esc]);
+    }
+
+    #[test]
+    fn bracketed_paste() {
+        assert_eq!(keys(b"\x1b[200~qb\tx\x1b[201~k"), [Key::Paste("qb\tx".to_string()), Key::Char('k')]);
+    }
+
+    #[test]
+    fn paste_survives_a_signal() {
+        let mut script = Script::new(b"\x1b[200~ab");
+        script.0.push_back(None);
+        script.0.extend(b"qb\x1b[201~".iter().copied().map(Some));
+        assert_eq!(decode_key(&mut script).unwrap(), Key::Paste("abqb".to_string()));
+        assert!(script.0.is_empty());
+    }
+
+    #[test]
+    fn intermediate_bytes_are_consumed_with_the_sequence() {
+        // DECSCUSR-style `CSI 1 SP q` is not a key; the final `q` must not become one
+        assert_eq!(keys(b"\x1b[1 qk"), [Key::Unknown, Key::Char('k')]);
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::HashSet;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Journal format: one decision per line, tab-separated, path last:
+//   <unix time>\t<action>\t<size>\t<mtime>\t<escaped path>\n
+// Each line is written with a single append and synced, so a crash can at worst
+// leave a truncated final line, which is ignored on load.
+
+const JOURNAL_FILE: &str = "journal.tsv";
+
+/// A specific version of a file: edited files (new size or mtime) count as unreviewed
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Fingerprint {
+    pub path: PathBuf,
+    pub size: u64,
+    pub mtime: i64,
+}
+
+impl Fingerprint {
+    /// Absolute path plus current size/mtime (None if the file is missing)
+    pub fn of(path: &Path) -> Option<Fingerprint> {
+        let meta = fs::metadata(path).ok()?;
+        let path = std::path::absolute(path).ok()?;
+        Some(Fingerprint { path, size: meta.len(), mtime: meta.mtime() })
+    }
+}
+
+/// Append-only record of every decision, across runs
+pub struct Journal {
+    file: File,
+}
+
+impl Journal {
+    /// Open (creating if needed) the journal in the state directory
+    pub fn open() -> io::Result<Journal> {
+        let path = journal_path()?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Journal { file })
+    }
+
+    /// Durably append one decision ("keep", "trash", "undo", ...)
+    pub fn record(&mut self, fingerprint: &Fingerprint, action: &str) -> io::Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let line = format!(
+            "{}\t{}\t{}\t{}\t{}\n",
+            timestamp,
+            action,
+            fingerprint.size,
+            fingerprint.mtime,
+            escape_path(&fingerprint.path)
+        );
+        self.file.write_all(line.as_bytes())?;
+        self.file.sync_data()
+    }
+}
+
+/// File versions with a standing decision, replaying "undo" lines as removals
+pub fn reviewed() -> io::Result<HashSet<Fingerprint>> {
+    let mut reviewed = HashSet::new();
+    let contents = match fs::read_to_string(journal_path()?) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(reviewed),
+        Err(e) => return Err(e),
+    };
+
+    // A final line without '\n' was cut short by a crash; drop it
+    let complete = if contents.ends_with('\n') {
+        contents.as_str()
+    } else {
+        contents.rsplit_once('\n').map(|(done, _)| done).unwrap_or("")
+    };
+    for line in complete.split_terminator('\n') {
+        let fields: Vec<&str> = line.splitn(5, '\t').collect();
+        let [_, action, size, mtime, path] = fields[..] else {
+            continue;
+        };
+        let (Ok(size), Ok(mtime)) = (size.parse(), mtime.parse()) else {
+            continue;
+        };
+        let fingerprint = Fingerprint { path: unescape_path(path), size, mtime };
+        if action == "undo" {
+            reviewed.remove(&fingerprint);
+        } else {
+            reviewed.insert(fingerprint);
+        }
+    }
+    Ok(reviewed)
+}
+
+/// `$XDG_STATE_HOME/piccy-picky/journal.tsv`, defaulting to `~/.local/state`
+fn journal_path() -> io::Result<PathBuf> {
+    let state_home = match env::var_os("XDG_STATE_HOME").filter(|v| !v.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            let home = env::var_os("HOME")
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "HOME is not set"))?;
+            PathBuf::from(home).join(".local/state")
+        }
+    };
+    Ok(state_home.join("piccy-picky").join(JOURNAL_FILE))
+}
+
+/// Keep each record on one line: escape backslash, tab and newline
+fn escape_path(path: &Path) -> String {
+    path.to_string_lossy()
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+fn unescape_path(escaped: &str) -> PathBuf {
+    let mut out = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    PathBuf::from(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escaped_paths_stay_on_one_field() {
+        let escaped = escape_path(Path::new("/a\tb\nc\\d"));
+        assert_eq!(escaped, "/a\\tb\\nc\\\\d");
+        assert!(!escaped.contains(['\t', '\n']));
+    }
+
+    #[test]
+    fn escape_round_trips() {
+        for path in ["/plain/photo.jpg", "/tab\there", "/new\nline", "/back\\slash", "/\\t literal", "/trailing\\", "\t\n\\\\n"] {
+            assert_eq!(unescape_path(&escape_path(Path::new(path))), PathBuf::from(path));
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use base64::Engine;
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+// kitty graphics protocol: https://sw.kovidgoyal.net/kitty/graphics-protocol/
+// Payloads are sent as APC escapes (ESC _ G <keys> ; <base64> ESC \) in chunks of at
+// most 4096 base64 bytes, with m=1 on every chunk except the last.
+
+const CHUNK_SIZE: usize = 4096;
+
+/// Next image id to assign (0 is reserved by the protocol for "no id")
+static NEXT_ID: AtomicU32 = AtomicU32::new(1);
+
+/// Ids of images currently placed on screen, deleted on redraw
+static PLACED: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+/// Transmit a PNG and place it at the cursor, `width_chars` cells wide
+///
+/// Only the column count is given, so kitty derives the row count from the
+/// image's aspect ratio (the same contract as iTerm2's `width=Nc`).
+pub fn display(png_data: &[u8], width_chars: u32) {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let encoded = base64::engine::general_purpose::STANDARD.encode(png_data);
+
+    let mut out = String::with_capacity(encoded.len() + encoded.len() / CHUNK_SIZE * 16 + 64);
+    let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(CHUNK_SIZE).collect();
+    for (idx, chunk) in chunks.iter().enumerate() {
+        let more = if idx + 1 < chunks.len() { 1 } else { 0 };
+        // base64 output is ASCII, so chunk boundaries are valid UTF-8
+        let chunk = std::str::from_utf8(chunk).unwrap_or_default();
+        if idx == 0 {
+            // a=T: transmit and display, f=100: PNG, q=2: suppress responses
+            out.push_str(&format!("\x1b_Ga=T,f=100,i={},c={},q=2,m={};{}\x1b\\",
+                                  id, width_chars, more, chunk));
+        } else {
+            out.push_str(&format!("\x1b_Gm={};{}\x1b\\", more, chunk));
+        }
+    }
+    println!("{}", out);
+
+    PLACED.lock().unwrap().push(id);
+}
+
+/// Delete all placements made so far and free their image data in the terminal
+pub fn delete_placements() {
+    let mut placed = PLACED.lock().unwrap();
+    let mut out = String::new();
+    for id in placed.drain(..) {
+        // d=I: delete placements of this image id and free the data
+        out.push_str(&format!("\x1b_Ga=d,d=I,i={},q=2\x1b\\", id));
+    }
+    print!("{}", out);
+    io::stdout().flush().unwrap();
+}
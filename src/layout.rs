@@ -0,0 +1,443 @@
+// Pure layout math: terminal metrics and image dimensions in, placements out.
+// Nothing here touches the terminal or the filesystem, so it can be tested directly.
+//
+// Images go in reading order (left to right, top to bottom) inside the image area:
+// the screen minus UI_ROWS at the bottom and WIDTH_MARGIN_COLS at the right. Each
+// image has a path line right below it. Terminals draw an image from its width in
+// cells and round its height up to whole rows, so that is exactly what we compute.
+
+/// Rows for the count line, prompt line and continue prompt below the images
+pub const UI_ROWS: u32 = 5;
+
+/// Columns kept free at the right so nothing touches the last column
+pub const WIDTH_MARGIN_COLS: u32 = 2;
+
+/// Blank columns between neighbouring images
+pub const GAP_COLS: u32 = 1;
+
+/// Path line under each image
+pub const LABEL_ROWS: u32 = 1;
+
+/// Terminal size in cells and pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Terminal {
+    pub cols: u16,
+    pub rows: u16,
+    pub px_width: u32,
+    pub px_height: u32,
+}
+
+impl Terminal {
+    /// Pixels per cell (width, height), never zero
+    pub fn cell_px(&self) -> (u32, u32) {
+        (
+            (self.px_width / self.cols.max(1) as u32).max(1),
+            (self.px_height / self.rows.max(1) as u32).max(1),
+        )
+    }
+
+    /// Cells available for images (width, height)
+    pub fn image_area(&self) -> (u32, u32) {
+        (
+            (self.cols as u32).saturating_sub(WIDTH_MARGIN_COLS),
+            (self.rows as u32).saturating_sub(UI_ROWS),
+        )
+    }
+}
+
+/// How images share the screen
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Strategy {
+    /// Same width for every image in a grid, scaled down together until it fits
+    #[default]
+    Uniform,
+    /// Equal boxes in a grid; each image as large as its own box allows
+    PerImage,
+    /// Same height for every image, flowed into rows like a photo gallery
+    EqualHeight,
+}
+
+impl Strategy {
+    /// Names accepted by `--fit`
+    pub const NAMES: &'static str = "uniform|per-image|equal-height";
+
+    pub fn from_name(name: &str) -> Option<Strategy> {
+        match name {
+            "uniform" => Some(Strategy::Uniform),
+            "per-image" => Some(Strategy::PerImage),
+            "equal-height" => Some(Strategy::EqualHeight),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Strategy::Uniform => "uniform",
+            Strategy::PerImage => "per-image",
+            Strategy::EqualHeight => "equal-height",
+        }
+    }
+}
+
+/// One image's box, in cells from the top-left of the screen
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Placement {
+    pub row: u32,
+    pub col: u32,
+    pub width_chars: u32,
+    /// Rows the image itself covers (the path line goes right below)
+    pub height_rows: u32,
+}
+
+/// Where each image of a batch goes
+#[derive(Clone, Debug)]
+pub struct Layout {
+    pub terminal: Terminal,
+    pub strategy: Strategy,
+    /// Grid columns (for equal-height: the most images in one row)
+    pub columns: u32,
+    /// Grid rows
+    pub rows: u32,
+    /// Same order as the dimensions passed to `compute`
+    pub placements: Vec<Placement>,
+    /// Rows used from the top, including path lines
+    pub height_rows: u32,
+}
+
+impl Layout {
+    pub fn cell_px(&self) -> (u32, u32) {
+        self.terminal.cell_px()
+    }
+
+    /// True if every image and its path line lies inside the image area
+    pub fn fits(&self) -> bool {
+        let (area_cols, area_rows) = self.terminal.image_area();
+        self.placements.iter().all(|p| {
+            p.col + p.width_chars <= area_cols && p.row + p.height_rows + LABEL_ROWS <= area_rows
+        })
+    }
+}
+
+/// Lay out images of the given pixel dimensions
+///
+/// If the strategy finds no arrangement that fits, uniform is used instead; if even
+/// that fails (a tiny terminal) every image is stacked 1 cell wide and may overflow.
+pub fn compute(terminal: Terminal, dims: &[(u32, u32)], strategy: Strategy) -> Layout {
+    let aspects: Vec<f32> = dims.iter().map(|&(w, h)| h.max(1) as f32 / w.max(1) as f32).collect();
+    let cell_px = terminal.cell_px();
+    let (area_cols, area_rows) = terminal.image_area();
+
+    let arranged = match strategy {
+        Strategy::Uniform => None,
+        Strategy::PerImage => per_image(&aspects, area_cols, area_rows, cell_px),
+        Strategy::EqualHeight => equal_height(&aspects, area_cols, area_rows, cell_px),
+    };
+    let (strategy, arrangement) = match arranged {
+        Some(arrangement) => (strategy, arrangement),
+        None => (
+            Strategy::Uniform,
+            uniform(&aspects, area_cols, area_rows, cell_px)
+                .unwrap_or_else(|| stack_uniform(&aspects, 1, 1, cell_px)),
+        ),
+    };
+    Layout {
+        terminal,
+        strategy,
+        columns: arrangement.columns,
+        rows: arrangement.rows,
+        height_rows: arrangement.height(),
+        placements: arrangement.placements,
+    }
+}
+
+/// Rows an image covers when drawn `width` cells wide (as terminals round: up)
+pub fn image_rows(aspect: f32, width: u32, cell_px: (u32, u32)) -> u32 {
+    let height_px = width as f32 * cell_px.0 as f32 * aspect;
+    ((height_px / cell_px.1.max(1) as f32).ceil() as u32).max(1)
+}
+
+/// Widest image that covers at most `rows` rows (0 if even 1 cell is too tall)
+fn width_for_rows(aspect: f32, rows: u32, cell_px: (u32, u32)) -> u32 {
+    let width = (rows as f32 * cell_px.1 as f32 / (aspect * cell_px.0 as f32)).floor() as u32;
+    // Float rounding can land one over; step back until the rounded-up height agrees
+    (0..=width).rev().find(|&w| w == 0 || image_rows(aspect, w, cell_px) <= rows).unwrap_or(0)
+}
+
+struct Arrangement {
+    columns: u32,
+    rows: u32,
+    placements: Vec<Placement>,
+}
+
+/// Image pixels shown by an arrangement, to compare candidates
+fn displayed_area(aspects: &[f32], placements: &[Placement], cell_px: (u32, u32)) -> u64 {
+    aspects
+        .iter()
+        .zip(placements)
+        .map(|(&aspect, p)| {
+            let width_px = (p.width_chars * cell_px.0) as f32;
+            (width_px * width_px * aspect) as u64
+        })
+        .sum()
+}
+
+/// Keep the candidate with the most displayed area; on a tie the earlier one (fewer columns)
+fn best_of(
+    aspects: &[f32],
+    cell_px: (u32, u32),
+    candidates: impl Iterator<Item = Arrangement>,
+) -> Option<Arrangement> {
+    let mut best: Option<(u64, Arrangement)> = None;
+    for candidate in candidates {
+        let area = displayed_area(aspects, &candidate.placements, cell_px);
+        if best.as_ref().is_none_or(|(best_area, _)| area > *best_area) {
+            best = Some((area, candidate));
+        }
+    }
+    best.map(|(_, arrangement)| arrangement)
+}
+
+/// Every column count, one shared width shrunk until the grid fits
+fn uniform(aspects: &[f32], area_cols: u32, area_rows: u32, cell_px: (u32, u32)) -> Option<Arrangement> {
+    let candidates = (1..=aspects.len().max(1) as u32).filter_map(|columns| {
+        let max_width = area_cols.saturating_sub((columns - 1) * GAP_COLS) / columns;
+        // Height only grows with width, so binary search for the last width that fits
+        let fits = |width: u32| stack_uniform(aspects, columns, width, cell_px).height() <= area_rows;
+        if max_width == 0 || !fits(1) {
+            return None;
+        }
+        let (mut lo, mut hi) = (1, max_width);
+        while lo < hi {
+            let mid = lo + (hi - lo).div_ceil(2);
+            if fits(mid) {
+                lo = mid;
+            } else {
+                hi = mid - 1;
+            }
+        }
+        Some(stack_uniform(aspects, columns, lo, cell_px))
+    });
+    best_of(aspects, cell_px, candidates)
+}
+
+/// Grid of `columns` with every image `width` cells wide, each row as tall as its tallest image
+fn stack_uniform(aspects: &[f32], columns: u32, width: u32, cell_px: (u32, u32)) -> Arrangement {
+    let mut placements = Vec::with_capacity(aspects.len());
+    let mut top = 0;
+    for row in aspects.chunks(columns as usize) {
+        let mut tallest = 0;
+        for (i, &aspect) in row.iter().enumerate() {
+            let height_rows = image_rows(aspect, width, cell_px);
+            tallest = tallest.max(height_rows);
+            placements.push(Placement { row: top, col: i as u32 * (width + GAP_COLS), width_chars: width, height_rows });
+        }
+        top += tallest + LABEL_ROWS;
+    }
+    Arrangement { columns, rows: aspects.len().div_ceil(columns as usize) as u32, placements }
+}
+
+impl Arrangement {
+    fn height(&self) -> u32 {
+        self.placements.iter().map(|p| p.row + p.height_rows + LABEL_ROWS).max().unwrap_or(0)
+    }
+}
+
+/// Every column count, the area split into equal boxes, each image fitted to its box
+fn per_image(aspects: &[f32], area_cols: u32, area_rows: u32, cell_px: (u32, u32)) -> Option<Arrangement> {
+    let candidates = (1..=aspects.len().max(1) as u32).filter_map(|columns| {
+        let grid_rows = aspects.len().div_ceil(columns as usize) as u32;
+        let box_cols = area_cols.saturating_sub((columns - 1) * GAP_COLS) / columns;
+        let box_rows = (area_rows / grid_rows.max(1)).saturating_sub(LABEL_ROWS);
+        if box_cols == 0 || box_rows == 0 {
+            return None;
+        }
+        let mut placements = Vec::with_capacity(aspects.len());
+        let mut top = 0;
+        for row in aspects.chunks(columns as usize) {
+            let mut tallest = 0;
+            for (i, &aspect) in row.iter().enumerate() {
+                let width = box_cols.min(width_for_rows(aspect, box_rows, cell_px));
+                if width == 0 {
+                    return None;
+                }
+                let height_rows = image_rows(aspect, width, cell_px);
+                tallest = tallest.max(height_rows);
+                placements.push(Placement { row: top, col: i as u32 * (box_cols + GAP_COLS), width_chars: width, height_rows });
+            }
+            // Rows shrink to their tallest image, so there's never more than the boxes used
+            top += tallest + LABEL_ROWS;
+        }
+        Some(Arrangement { columns, rows: grid_rows, placements })
+    });
+    best_of(aspects, cell_px, candidates)
+}
+
+/// The tallest shared height at which the images, flowed left to right, fit the area
+fn equal_height(aspects: &[f32], area_cols: u32, area_rows: u32, cell_px: (u32, u32)) -> Option<Arrangement> {
+    // Taller images are wider too, so needed rows grow with the height: take the first that fits
+    (1..=area_rows.saturating_sub(LABEL_ROWS))
+        .rev()
+        .find_map(|height| flow_equal_height(aspects, height, area_cols, cell_px).filter(|a| a.height() <= area_rows))
+}
+
+/// Flow images `height` rows tall into lines no wider than `area_cols`
+fn flow_equal_height(aspects: &[f32], height: u32, area_cols: u32, cell_px: (u32, u32)) -> Option<Arrangement> {
+    let mut placements = Vec::with_capacity(aspects.len());
+    let (mut line, mut x, mut in_line, mut columns) = (0, 0, 0, 0);
+    for &aspect in aspects {
+        let width = width_for_rows(aspect, height, cell_px);
+        if width == 0 || width > area_cols {
+            return None;
+        }
+        if in_line > 0 && x + GAP_COLS + width > area_cols {
+            line += 1;
+            x = 0;
+            in_line = 0;
+        }
+        if in_line > 0 {
+            x += GAP_COLS;
+        }
+        placements.push(Placement {
+            row: line * (height + LABEL_ROWS),
+            col: x,
+            width_chars: width,
+            height_rows: image_rows(aspect, width, cell_px),
+        });
+        x += width;
+        in_line += 1;
+        columns = columns.max(in_line);
+    }
+    let rows = if placements.is_empty() { 0 } else { line + 1 };
+    Some(Arrangement { columns, rows, placements })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::{prop, prop_assert, prop_assert_eq, prop_assume, proptest};
+
+    const STRATEGIES: [Strategy; 3] = [Strategy::Uniform, Strategy::PerImage, Strategy::EqualHeight];
+
+    fn terminal(cols: u16, rows: u16, cell_px: (u32, u32)) -> Terminal {
+        Terminal { cols, rows, px_width: cols as u32 * cell_px.0, px_height: rows as u32 * cell_px.1 }
+    }
+
+    fn aspect((w, h): (u32, u32)) -> f32 {
+        h.max(1) as f32 / w.max(1) as f32
+    }
+
+    /// Images (with their path lines) never share a cell
+    fn overlaps(a: &Placement, b: &Placement) -> bool {
+        a.col < b.col + b.width_chars
+            && b.col < a.col + a.width_chars
+            && a.row < b.row + b.height_rows + LABEL_ROWS
+            && b.row < a.row + a.height_rows + LABEL_ROWS
+    }
+
+    #[test]
+    fn portrait_images_go_side_by_side_on_a_wide_terminal() {
+        let layout = compute(terminal(200, 50, (8, 16)), &[(300, 600); 3], Strategy::Uniform);
+        assert_eq!(layout.columns, 3);
+        assert!(layout.fits());
+    }
+
+    #[test]
+    fn single_landscape_image_uses_the_full_width() {
+        let term = terminal(100, 60, (8, 16));
+        let layout = compute(term, &[(1600, 900)], Strategy::Uniform);
+        assert_eq!(layout.placements[0].width_chars, term.image_area().0);
+    }
+
+    #[test]
+    fn equal_height_gives_every_image_the_same_height() {
+        let dims = [(400, 300), (300, 400), (1000, 500), (500, 500)];
+        let layout = compute(terminal(160, 45, (9, 18)), &dims, Strategy::EqualHeight);
+        assert_eq!(layout.strategy, Strategy::EqualHeight);
+        let heights: Vec<u32> = layout.placements.iter().map(|p| p.height_rows).collect();
+        let lines: Vec<u32> = layout.placements.iter().map(|p| p.row).collect();
+        // Width is floored, so a height can come out one row short, never over
+        let tallest = *heights.iter().max().unwrap();
+        assert!(heights.iter().all(|&h| h + 1 >= tallest), "{:?} {:?}", heights, lines);
+        assert!(layout.fits());
+    }
+
+    #[test]
+    fn per_image_fills_each_box() {
+        // A panorama and a portrait: uniform gives both the portrait's width
+        let dims = [(3000, 1000), (1000, 1500)];
+        let term = terminal(120, 40, (8, 16));
+        let uniform = compute(term, &dims, Strategy::Uniform);
+        assert_eq!(uniform.placements[0].width_chars, uniform.placements[1].width_chars);
+        let per_image = compute(term, &dims, Strategy::PerImage);
+        assert_eq!(per_image.strategy, Strategy::PerImage);
+        assert!(per_image.placements[0].width_chars > per_image.placements[1].width_chars);
+        assert!(per_image.fits());
+    }
+
+    #[test]
+    fn too_small_terminal_falls_back_to_uniform() {
+        let layout = compute(terminal(10, 6, (8, 16)), &[(100, 5000)], Strategy::EqualHeight);
+        assert_eq!(layout.strategy, Strategy::Uniform);
+        assert_eq!(layout.placements.len(), 1);
+    }
+
+    #[test]
+    fn strategy_names_round_trip() {
+        for strategy in STRATEGIES {
+            assert_eq!(Strategy::from_name(strategy.name()), Some(strategy));
+        }
+    }
+
+    fn dims_strategy() -> impl proptest::strategy::Strategy<Value = Vec<(u32, u32)>> {
+        prop::collection::vec((1u32..8000, 1u32..8000), 1..=12)
+    }
+
+    proptest! {
+        /// Whenever a 1-cell-wide stack would fit, every strategy fits
+        #[test]
+        fn never_overflows(
+            cols in 10u16..400,
+            rows in 8u16..150,
+            cell_w in 4u32..24,
+            cell_h in 8u32..48,
+            dims in dims_strategy(),
+        ) {
+            let term = terminal(cols, rows, (cell_w, cell_h));
+            let (_, area_rows) = term.image_area();
+            let stacked: u32 = dims
+                .iter()
+                .map(|&d| image_rows(aspect(d), 1, term.cell_px()) + LABEL_ROWS)
+                .sum();
+            prop_assume!(stacked <= area_rows);
+            for strategy in STRATEGIES {
+                let layout = compute(term, &dims, strategy);
+                prop_assert!(layout.fits(), "{:?} overflowed: {:?}", strategy, layout);
+            }
+        }
+
+        /// Placements are sized the way the terminal will draw them and never overlap
+        #[test]
+        fn placements_are_consistent(
+            cols in 1u16..400,
+            rows in 1u16..150,
+            cell_w in 1u32..24,
+            cell_h in 1u32..48,
+            dims in dims_strategy(),
+        ) {
+            let term = terminal(cols, rows, (cell_w, cell_h));
+            for strategy in STRATEGIES {
+                let layout = compute(term, &dims, strategy);
+                prop_assert_eq!(layout.placements.len(), dims.len());
+                for (p, &d) in layout.placements.iter().zip(&dims) {
+                    prop_assert!(p.width_chars >= 1);
+                    prop_assert_eq!(p.height_rows, image_rows(aspect(d), p.width_chars, term.cell_px()));
+                }
+                for (i, a) in layout.placements.iter().enumerate() {
+                    for b in &layout.placements[i + 1..] {
+                        prop_assert!(!overlaps(a, b), "{:?} overlaps {:?}", a, b);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -4,6 +4,8 @@ use objc::{class, sel, sel_impl};
 use std::ffi::CString;
 use std::path::{Path, PathBuf};
 
+use crate::walk;
+
 pub fn request_folder_access(initial_path: &str) -> Option<PathBuf> {
     unsafe {
         let panel: *mut Object = msg_send![class!(NSOpenPanel), openPanel];
@@ -33,7 +35,6 @@ pub fn request_folder_access(initial_path: &str) -> Option<PathBuf> {
 
 /// Find images using FileManager.DirectoryEnumerator (handles firmlinks natively)
 pub fn find_images(path: &str, max_depth: usize) -> Vec<PathBuf> {
-    let image_extensions = ["jpg", "jpeg", "png", "gif", "webp", "bmp"];
     let mut images = Vec::new();
     
     unsafe {
@@ -85,28 +86,14 @@ pub fn find_images(path: &str, max_depth: usize) -> Vec<PathBuf> {
             let path_buf = Path::new(path_str.as_ref());
             
             // Skip system/trash directories
-            let skip_dirs = [".Trash", ".Volumes", ".TemporaryItems", ".DS_Store"];
-            let mut skip = false;
-            for component in path_buf.components() {
-                if let Some(name) = component.as_os_str().to_str() {
-                    if skip_dirs.contains(&name) {
-                        skip = true;
-                        break;
-                    }
-                }
-            }
-            if skip {
+            if walk::is_skipped(path_buf) {
                 let _: () = msg_send![enumerator, skipDescendants];
                 continue;
             }
             
             // Check if file has image extension
-            if let Some(ext) = path_buf.extension() {
-                if let Some(ext_str) = ext.to_str() {
-                    if image_extensions.contains(&ext_str.to_lowercase().as_str()) {
-                        images.push(path_buf.to_path_buf());
-                    }
-                }
+            if walk::has_image_extension(path_buf) {
+                images.push(path_buf.to_path_buf());
             }
         }
     }
@@ -114,37 +101,72 @@ pub fn find_images(path: &str, max_depth: usize) -> Vec<PathBuf> {
     images
 }
 
-pub fn move_to_trash(path: &Path) -> bool {
+/// Move a file to the Trash, returning where it ended up so it can be restored
+pub fn move_to_trash(path: &Path) -> Option<PathBuf> {
     unsafe {
         let fm: *mut Object = msg_send![class!(NSFileManager), defaultManager];
-
-        let path_str = path.to_string_lossy();
-        let c_path = CString::new(path_str.as_bytes()).unwrap();
-        let path_obj: *mut Object =
-            msg_send![class!(NSString), stringWithUTF8String: c_path.as_ptr()];
+        let url = file_url(path);
 
         // Use trashItemAtURL:resultingItemURL:error:
         // This moves to trash without overwriting items with the same name
-        let url: *mut Object = msg_send![class!(NSURL), fileURLWithPath: path_obj];
+        let mut error: *mut Object = std::ptr::null_mut();
+        let mut result_url: *mut Object = std::ptr::null_mut();
+        let success: bool = msg_send![fm, trashItemAtURL:url resultingItemURL:&mut result_url error:&mut error];
+
+        if !error.is_null() {
+            print_ns_error(error);
+            return None;
+        }
+        if !success || result_url.is_null() {
+            return None;
+        }
+
+        let path_obj: *mut Object = msg_send![result_url, path];
+        let c_str: *const i8 = msg_send![path_obj, UTF8String];
+        let trashed = std::ffi::CStr::from_ptr(c_str).to_string_lossy();
+        Some(PathBuf::from(trashed.to_string()))
+    }
+}
+
+/// Move a trashed file back to its original path (fails rather than overwrite)
+pub fn restore_from_trash(trashed: &Path, original: &Path) -> bool {
+    unsafe {
+        let fm: *mut Object = msg_send![class!(NSFileManager), defaultManager];
+        let from = file_url(trashed);
+        let to = file_url(original);
 
         let mut error: *mut Object = std::ptr::null_mut();
-        let result_url: *mut Object = std::ptr::null_mut();
-        let success: bool = msg_send![fm, trashItemAtURL:url resultingItemURL:&result_url error:&mut error];
+        let success: bool = msg_send![fm, moveItemAtURL:from toURL:to error:&mut error];
 
         if !error.is_null() {
-            let err_desc: *mut Object = msg_send![error, description];
-            let c_str: *const i8 = msg_send![err_desc, UTF8String];
-            eprintln!(
-                "NSError: {}",
-                std::ffi::CStr::from_ptr(c_str).to_string_lossy()
-            );
+            print_ns_error(error);
             return false;
         }
-
         success
     }
 }
 
+unsafe fn file_url(path: &Path) -> *mut Object {
+    let path_str = path.to_string_lossy();
+    let c_path = CString::new(path_str.as_bytes()).unwrap();
+    unsafe {
+        let path_obj: *mut Object =
+            msg_send![class!(NSString), stringWithUTF8String: c_path.as_ptr()];
+        msg_send![class!(NSURL), fileURLWithPath: path_obj]
+    }
+}
+
+unsafe fn print_ns_error(error: *mut Object) {
+    unsafe {
+        let err_desc: *mut Object = msg_send![error, description];
+        let c_str: *const i8 = msg_send![err_desc, UTF8String];
+        eprintln!(
+            "NSError: {}",
+            std::ffi::CStr::from_ptr(c_str).to_string_lossy()
+        );
+    }
+}
+
 /// Launch QuickLook preview for a file
 pub fn quicklook_preview(path: &Path) {
     use std::process::{Command, Stdio};
@@ -2,32 +2,80 @@
 
 use rand::seq::SliceRandom;
 use std::env;
-use std::io::{self, Write, Cursor};
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
-use image::GenericImageView;
 
+#[cfg(target_os = "macos")]
 mod macos;
+mod kitty;
+mod blocks;
+mod cache;
+mod destinations;
+mod detect;
+mod dry_run;
+mod formats;
+mod input;
+mod journal;
+mod layout;
+mod metadata;
+mod prefetch;
+mod render;
+mod review;
+mod sixel;
 mod term;
+mod walk;
+#[cfg(not(target_os = "macos"))]
+mod xdg;
 
-// Single scaling algorithm implemented:
-// 1. Fit each image to available width (in pixels)
-// 2. If all 3 heights exceed available height, scale all down uniformly
+#[cfg(target_os = "macos")]
+use macos as platform;
+#[cfg(not(target_os = "macos"))]
+use xdg as platform;
+
+use destinations::{Destination, Destinations};
+use input::Key;
+use journal::{Fingerprint, Journal};
+use layout::{Layout, Strategy, Terminal};
+use prefetch::Prefetch;
+use render::{Encoding, Renderer};
+
+// Layout (see layout.rs), with the default uniform strategy:
+// 1. Try every column count, giving all images the same width in cells
+// 2. Shrink that width until the grid's total height fits the screen
+// 3. Keep the arrangement that shows the most image pixels
 // Uniform scaling ensures all images scale proportionally together
 
+/// Images shown at once unless --batch says otherwise
+const DEFAULT_BATCH_SIZE: usize = 3;
+
+/// Beyond this the images get too small to judge, even on a large terminal
+const MAX_BATCH_SIZE: usize = 12;
+
+/// Accepted range for --hidpi
+const MIN_HIDPI_SCALE: f32 = 0.25;
+const MAX_HIDPI_SCALE: f32 = 4.0;
+
 fn main() {
     let args: Vec<String> = env::args().collect();
     if args.len() < 2 {
-        eprintln!("Usage: piccy-picky [OPTIONS] <path> [path2] ...");
-        eprintln!("Options:");
-        eprintln!("  -d, --depth <N>      Search depth (default: 1)");
-        eprintln!("  --test-search        Test file search only (print results and exit)");
+        eprint!("{}", usage());
         std::process::exit(1);
     }
 
     // Parse CLI args
     let mut target_paths = Vec::new();
     let mut depth = 1usize;
+    let mut batch_size = DEFAULT_BATCH_SIZE;
+    let mut fit = Strategy::default();
     let mut test_search = false;
+    let mut renderer: Option<Renderer> = None;
+    let mut cell_size: Option<(u32, u32)> = None;
+    let mut encoding = Encoding::default();
+    let mut resume = false;
+    let mut defer = false;
+    let mut destination_args: Vec<(String, destinations::Mode)> = Vec::new();
+    let mut dry_run = false;
+    let mut dry_run_report: Option<PathBuf> = None;
     let mut i = 1;
     
     while i < args.len() {
@@ -38,6 +86,109 @@ fn main() {
                     depth = args[i].parse().unwrap_or(1);
                 }
             }
+            "--batch" => {
+                i += 1;
+                match args.get(i).and_then(|n| n.parse().ok()) {
+                    Some(n) if (1..=MAX_BATCH_SIZE).contains(&n) => batch_size = n,
+                    _ => {
+                        eprintln!("Error: --batch expects a number from 1 to {}", MAX_BATCH_SIZE);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--fit" => {
+                i += 1;
+                match args.get(i).and_then(|name| Strategy::from_name(name)) {
+                    Some(strategy) => fit = strategy,
+                    None => {
+                        eprintln!("Error: --fit expects one of {}", Strategy::NAMES);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--renderer" => {
+                i += 1;
+                match args.get(i).map(|name| name.as_str()) {
+                    Some("auto") => renderer = None,
+                    Some(name) if Renderer::from_name(name).is_some() => {
+                        renderer = Renderer::from_name(name);
+                    }
+                    _ => {
+                        eprintln!("Error: --renderer expects one of auto|{}", Renderer::NAMES);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--hidpi" => {
+                i += 1;
+                match args.get(i).and_then(|scale| scale.parse::<f32>().ok()) {
+                    Some(scale) if (MIN_HIDPI_SCALE..=MAX_HIDPI_SCALE).contains(&scale) => encoding.hidpi_scale = scale,
+                    _ => {
+                        eprintln!("Error: --hidpi expects a scale from {} to {}", MIN_HIDPI_SCALE, MAX_HIDPI_SCALE);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--jpeg-quality" => {
+                i += 1;
+                match args.get(i).and_then(|n| n.parse().ok()) {
+                    Some(quality) if (1..=100).contains(&quality) => encoding.jpeg_quality = quality,
+                    _ => {
+                        eprintln!("Error: --jpeg-quality expects a number from 1 to 100");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--max-payload" => {
+                i += 1;
+                encoding.max_payload = args.get(i).and_then(|size| parse_size(size));
+                if encoding.max_payload.is_none() {
+                    eprintln!("Error: --max-payload expects a size in bytes, e.g. 500000, 300K or 2M");
+                    std::process::exit(1);
+                }
+            }
+            "--cell-size" => {
+                i += 1;
+                cell_size = args.get(i).and_then(|size| parse_cell_size(size));
+                if cell_size.is_none() {
+                    eprintln!("Error: --cell-size expects WIDTHxHEIGHT in pixels, e.g. 8x16");
+                    std::process::exit(1);
+                }
+            }
+            "--resume" => {
+                resume = true;
+            }
+            "--move-to" | "--copy-to" => {
+                let mode = if args[i] == "--move-to" { destinations::Mode::Move } else { destinations::Mode::Copy };
+                i += 1;
+                match args.get(i) {
+                    Some(dir) => destination_args.push((dir.clone(), mode)),
+                    None => {
+                        eprintln!("Error: {} expects a directory, optionally prefixed with a key: 1=DIR", args[i - 1]);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--defer" => {
+                defer = true;
+            }
+            "--dry-run" => {
+                dry_run = true;
+            }
+            "--dry-run-report" => {
+                i += 1;
+                match args.get(i) {
+                    Some(file) => dry_run_report = Some(PathBuf::from(file)),
+                    None => {
+                        eprintln!("Error: --dry-run-report expects a file path");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "-h" | "--help" => {
+                print!("{}", usage());
+                std::process::exit(0);
+            }
             "--test-search" => {
                 test_search = true;
             }
@@ -56,12 +207,28 @@ fn main() {
         eprintln!("Error: at least one path required");
         std::process::exit(1);
     }
+    if dry_run_report.is_some() && !dry_run {
+        eprintln!("Error: --dry-run-report requires --dry-run");
+        std::process::exit(1);
+    }
+
+    // Number-key destinations: config file first, command line overrides per key
+    let mut destinations = Destinations::load_config().unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    });
+    for (arg, mode) in &destination_args {
+        if let Err(e) = destinations.bind_arg(arg, *mode) {
+            eprintln!("Error: {:?}: {}", arg, e);
+            std::process::exit(1);
+        }
+    }
     
     // If test mode, just search and print results
     if test_search {
         let mut all_images = Vec::new();
         for path in &target_paths {
-            let images = macos::find_images(path, depth);
+            let images = platform::find_images(path, depth);
             all_images.extend(images);
         }
         println!("Found {} image files:", all_images.len());
@@ -92,132 +259,99 @@ fn main() {
         }
         }
 
+        // Every decision is journaled so later runs can --resume; triage still works without it
+        // A dry run leaves no trace, so it isn't journaled (and can't satisfy a later --resume)
+        let mut journal = if dry_run {
+            None
+        } else {
+            match journal::Journal::open() {
+                Ok(journal) => Some(journal),
+                Err(e) => {
+                    eprintln!("Warning: decision journal unavailable: {}", e);
+                    None
+                }
+            }
+        };
+
         // Enable raw mode for interactive input
-        let original_termios = term::enable_raw_mode()
+        // Restored when the guard drops, and by the handlers on signals/panics
+        let terminal = term::RawModeGuard::enable()
         .expect("Failed to enable raw mode");
-        
-        // Ensure we restore on exit
-        let _restore = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-        // Deferred cleanup via drop
-        }));
+        term::install_exit_handlers();
+
+        term::install_resize_handler();
+
+        // Probe the terminal (needs raw mode so replies are not echoed) unless overridden
+        let mut renderer = renderer.unwrap_or_else(detect::detect_renderer);
+
+        // Cell metrics for when TIOCGWINSZ reports no pixels (tmux, SSH, many Linux terminals):
+        // XTWINOPS query, then --cell-size, and only then an assumed 1:2 cell
+        let mut fallback_cell_px = None;
+        if term::get_terminal_pixel_size().is_none() {
+            let (cols, rows) = term::get_terminal_size();
+            fallback_cell_px = term::query_cell_pixel_size(cols, rows).or(cell_size);
+            if fallback_cell_px.is_none() && renderer == Renderer::Sixel {
+                // Sixel is sized in pixels, so without real cell metrics it would be guesswork
+                renderer = Renderer::Blocks;
+            }
+        }
+        let fallback_cell_px = fallback_cell_px.unwrap_or(term::ASSUMED_CELL_PX);
 
         let mut chosen: Option<Vec<PathBuf>> = None;
+        // The batch after this one, decoding in the background
+        let mut prefetch: Option<Prefetch> = None;
 
         // Scan all images once at the start
         let mut images = Vec::new();
         for path in &target_paths {
-            let path_images = macos::find_images(path, depth);
+            let path_images = platform::find_images(path, depth);
             images.extend(path_images);
         }
+        if resume {
+            match journal::reviewed() {
+                Ok(reviewed) => {
+                    let before = images.len();
+                    images.retain(|p| !journal::Fingerprint::of(p).is_some_and(|fp| reviewed.contains(&fp)));
+                    println!("⏭️  Skipping {} images reviewed in earlier sessions", before - images.len());
+                }
+                Err(e) => eprintln!("Warning: could not read decision journal: {}", e),
+            }
+        }
         if images.is_empty() {
             println!("No images found in paths: {}", target_paths.join(", "));
+            drop(terminal);
             std::process::exit(0);
         }
 
-        loop {
-        // Get terminal dimensions
-        // CRITICAL: These are our single source of truth for layout calculations.
-        // We work primarily in pixels for precision, then convert to character dimensions only for iTerm2.
-        let (cols, rows) = term::get_terminal_size();           // Character grid dimensions
-        let (px_width, px_height) = term::get_terminal_pixel_size(); // Pixel dimensions of terminal
+        // Every decision this session, most recent last, for undo
+        let mut history: Vec<Decision> = Vec::new();
 
+        'session: loop {
         // Check if we've run out of images
         if images.is_empty() {
             println!("\n✨ All images reviewed! No more to pick from.");
             break;
         }
 
-        // Pick 3 new images
+        // Pick a new batch: the prefetched one, minus anything since decided, topped up at random
         if chosen.is_none() {
-            let mut rng = rand::thread_rng();
-            let batch_size = 3.min(images.len());
-            chosen = Some(
-                images
-                    .choose_multiple(&mut rng, batch_size)
-                    .cloned()
-                    .collect()
-            );
+            let mut batch = prefetch.take().map(Prefetch::finish).unwrap_or_default();
+            batch.retain(|p| images.contains(p));
+            let more = pick_batch(&images, &batch, batch_size - batch.len());
+            batch.extend(more);
+            chosen = Some(batch);
         }
         
         let chosen_ref = chosen.as_ref().unwrap();
-        
-        // ===== SCALING ALGORITHM =====
-        // Goal: Fit 3 images in available space without double-scaling
-        //
-        // Step 1: Calculate available space in PIXELS (not characters)
-        //   - UI needs ~5 rows = 5 * (px_height/rows)
-        //   - Available height in pixels = px_height - ui_rows_px
-        //   - Available width in pixels = responsive to terminal width, not hardcoded to 35 chars
-        //
-        // Step 2: For each image, calculate scaled dimensions
-        //   - We calculate what pixel width it should be: responsive to terminal width
-        //   - Use aspect ratio to get corresponding height in pixels
-        //   - NO pre-scaling of images during encoding (except for massive images >4000px)
-        //
-        // Step 3: Check if 3 scaled images fit vertically
-        //   - Sum pixel heights of 3 images + padding
-        //   - If over budget: calculate uniform scale-down factor (applies to all 3 equally)
-        //
-        // Step 4: Pass final pixel dimensions to load_and_display_image()
-        //   - Only apply scale during encoding if needed for size
-        //   - Let iTerm2 do the final scaling via width parameter
-        //
-        // KEY: Never scale twice. Our calculations tell iTerm2 exactly what to display.
-        
-        // Available space in PIXELS
-        let ui_rows = 5u32;
-        let ui_height_px = ui_rows * (px_height / rows.max(1) as u32);
-        let available_height_px = px_height.saturating_sub(ui_height_px);
-        
-        // Available width: responsive to terminal, with margin for safety
-        let width_margin_cols = 2u32;
-        let available_width_cols = cols.saturating_sub(width_margin_cols as u16) as u32;
-        let available_width_px = available_width_cols * (px_width / cols.max(1) as u32);
-        
-        // Use the full available width for display, not hardcoded 35 chars
-         let display_width_chars = available_width_cols;
-         let pixels_per_char_h = px_height.max(1) / rows.max(1) as u32;
-         let pixels_per_char_w = px_width.max(1) / cols.max(1) as u32;
-         let available_rows = rows.saturating_sub(5) as u32; // 5 rows reserved
-         
-         // STEP 1: Calculate scale factor needed to fit all 3 images vertically
-         // For each image: given display_width_chars and its aspect ratio, what height does it need?
-         // If sum of heights > available height, scale down all 3 uniformly
-         let mut scale_factor = 1.0f32;
-         let mut total_height_rows = 0u32;
-         
-         for path in chosen_ref {
-             match calc_image_height_rows(path, display_width_chars, pixels_per_char_w, pixels_per_char_h) {
-                 Ok(h) => {
-                     total_height_rows += h;
-                 }
-                 Err(e) => {
-                     let abbrev = term::abbreviate_path(path, "", cols as usize);
-                     eprintln!("Failed to calc height {}: {}", abbrev, e);
-                 }
-             }
-         }
-         
-         // If total height exceeds available, calculate uniform scale-down
-         // Add 2% safety buffer for rounding errors (ceil when converting px to rows)
-         if total_height_rows > available_rows {
-             scale_factor = (available_rows as f32 / total_height_rows as f32) * 0.98;
-         }
-
-         // Load and display images
-          // Scale the display width by our layout_scale factor, then let iTerm2 handle all rendering
-          // This avoids double-scaling: we reduce the width budget, iTerm2 scales image to fit
-          let scaled_display_width_chars = ((display_width_chars as f32) * scale_factor) as u32;
-         let mut displayed: Vec<(PathBuf, ImageInfo)> = Vec::new();
-         for path in chosen_ref {
-             match load_and_display_image(path, scaled_display_width_chars) {
-                Ok(info) => {
-                    let abbrev = term::abbreviate_path(path, "", cols as usize);
-                    println!("{}", abbrev);
-                    displayed.push((path.clone(), info));
-                }
+        let mut layout = compute_layout(chosen_ref, fallback_cell_px, fit);
+
+        // Load and display images at their places in the grid
+        let mut displayed: Vec<(PathBuf, ImageInfo)> = Vec::new();
+        for (path, result) in chosen_ref.iter().zip(draw_batch(chosen_ref, &layout, renderer, encoding)) {
+            match result {
+                Ok(info) => displayed.push((path.clone(), info)),
                 Err(e) => {
-                    let abbrev = term::abbreviate_path(path, "", cols as usize);
+                    let abbrev = term::abbreviate_path(path, "", layout.terminal.cols as usize);
                     eprintln!("Failed to load {}: {}", abbrev, e);
                 }
             }
@@ -227,216 +361,574 @@ fn main() {
             println!("Could not display any images.");
             break;
         }
+        if displayed.len() < chosen_ref.len() {
+            // Close the gaps; from here on the layout always covers a suffix of `displayed`
+            layout = redraw_undecided(&mut displayed, fallback_cell_px, fit, renderer, encoding, images.len());
+        }
 
         // Show count before prompts
         println!("📸 Picked {} images out of {}", chosen_ref.len(), images.len());
+        if !destinations.is_empty() {
+            let keys: Vec<String> = destinations
+                .iter()
+                .map(|(key, dest)| format!("{} {} {}", key, dest.mode.name(), dest.dir.display()))
+                .collect();
+            println!("📁 {}", keys.join("  "));
+        }
+
+        // Prepare the next batch while this one is being decided
+        let next = pick_batch(&images, chosen_ref, batch_size);
+        if !next.is_empty() {
+            prefetch = Some(Prefetch::start(next, measure_terminal(fallback_cell_px), renderer, encoding, fit));
+        }
 
         // Interactive interface: show [k/b/i] [k/b/i] [k/b/i] with ANSI highlighting
-         let mut decisions = Vec::new();
-        
-        for idx in 0..displayed.len() {
-            let (path, info) = &displayed[idx];
-            let abbrev = term::abbreviate_path(path, "", cols as usize - 20);
+        let mut decisions: Vec<String> = Vec::new();
+        let mut idx = 0;
+
+        'batch: loop {
+        while idx < displayed.len() {
+            // Terminal resized: re-layout the undecided images for the new size
+            if term::take_resized() {
+                layout = redraw_undecided(&mut displayed[idx..], fallback_cell_px, fit, renderer, encoding, images.len());
+            }
+            let path = displayed[idx].0.clone();
+            // Each slot is 6 columns wide; the path gets what's left
+            let abbrev = term::abbreviate_path(&path, "", (layout.terminal.cols as usize).saturating_sub(displayed.len() * 6 + 3));
+
+            // Build display line with a slot per image
+            let mut line = String::new();
+            for i in 0..displayed.len() {
+                if i == idx {
+                    // Current: bold
+                    line.push_str("\x1b[1m[k/b]\x1b[0m ");
+                } else if let Some(decision) = decisions.get(i) {
+                    // Done: show what was chosen (see Action::label)
+                    line.push_str(&format!("{:<6}", format!("[{}]", decision)));
+                } else {
+                    // Upcoming: dim
+                    line.push_str("\x1b[2m[k/b]\x1b[0m ");
+                }
+            }
+            line.push_str(&format!("  {}", abbrev));
             
-            loop {
-                // Build display line with all 3 slots
-                let mut line = String::new();
-                for i in 0..displayed.len() {
-                    if i == idx {
-                        // Current: bold
-                        line.push_str(&format!("\x1b[1m[k/b]\x1b[0m "));
-                    } else if i < idx {
-                        // Done: show what was chosen
-                        line.push_str(&format!("[{}]   ", decisions[i]));
+            print!("\r\x1b[K{}", line); // \r = carriage return, \x1b[K = clear to end of line
+            io::stdout().flush().unwrap();
+
+            // Read single keypress
+            let key = match input::read_key() {
+                Ok(key) => key,
+                // Signal (e.g. resize) while waiting: handled at the top of the loop
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => break 'session, // stdin closed
+            };
+
+            // Case matters for 'i' vs 'I' and 'C'; other letters are case-insensitive
+            match key {
+                Key::Ctrl('l') => {
+                    // Ctrl+L = clear screen and redraw undecided images
+                    layout = redraw_undecided(&mut displayed[idx..], fallback_cell_px, fit, renderer, encoding, images.len());
+                }
+                Key::Char('I') => {
+                    // Capital [I]: show comprehensive info for the whole batch + calculations
+                    let shown = displayed.len().saturating_sub(layout.placements.len());
+                    display_full_scaling_info(&displayed[shown..], &layout, renderer);
+                    // Wait for keypress
+                    let _ = input::read_key();
+                    println!("\n");
+                }
+                Key::Char('i') => {
+                    // Lowercase [i]: show info for current image only
+                    let shown = displayed.len().saturating_sub(layout.placements.len());
+                    let place = idx.checked_sub(shown).and_then(|i| layout.placements.get(i));
+                    display_image_info(&path, &displayed[idx].1, &layout, place);
+
+                    // Wait for keypress
+                    let _ = input::read_key();
+                    println!("\n"); // Clear and restart
+                }
+                Key::Char('k' | 'K') => {
+                    decisions.push(Action::Keep.label());
+                    record_decision(&mut journal, Fingerprint::of(&path), "keep");
+                    history.push(Decision { path: path.clone(), action: Action::Keep });
+                    // Remove from collection
+                    images.retain(|p| *p != path);
+                    idx += 1;
+                }
+                Key::Char('b' | 'B') if dry_run => {
+                    // Only record the intent; the file stays where it is
+                    decisions.push(Action::WouldTrash.label());
+                    history.push(Decision { path: path.clone(), action: Action::WouldTrash });
+                    images.retain(|p| *p != path);
+                    idx += 1;
+                }
+                Key::Char('b' | 'B') if defer => {
+                    // Trashed later, from the review screen
+                    decisions.push(Action::Marked.label());
+                    history.push(Decision { path: path.clone(), action: Action::Marked });
+                    images.retain(|p| *p != path);
+                    idx += 1;
+                }
+                Key::Char('C') if defer => {
+                    // Commit: review and apply the marks so far, then back to this batch
+                    review::review_marked(&mut history, &mut journal, renderer);
+                    refresh_decisions(&mut decisions, &displayed, &history);
+                    layout = redraw_undecided(&mut displayed[idx..], fallback_cell_px, fit, renderer, encoding, images.len());
+                }
+                Key::Char('b' | 'B') => {
+                    // Fingerprint first: the file is gone from its path once trashed
+                    let fingerprint = Fingerprint::of(&path);
+                    if let Some(trashed) = platform::move_to_trash(&path) {
+                        let action = Action::Trash(trashed);
+                        decisions.push(action.label());
+                        record_decision(&mut journal, fingerprint, "trash");
+                        history.push(Decision { path: path.clone(), action });
+                        // Remove from collection
+                        images.retain(|p| *p != path);
+                        idx += 1;
                     } else {
-                        // Upcoming: dim
-                        line.push_str("\x1b[2m[k/b]\x1b[0m ");
+                        print!("\x07"); // Bell on failure
+                        io::stdout().flush().unwrap();
                     }
                 }
-                line.push_str(&format!("  {}", abbrev));
-                
-                print!("\r\x1b[K{}", line); // \r = carriage return, \x1b[K = clear to end of line
-                io::stdout().flush().unwrap();
-
-                // Read single keypress
-                if let Ok(c) = term::read_single_char() {
-                    let code = c as u32;
-                    
-                    // Ctrl+L = clear screen and redraw undecided images
-                    if code == 12 {
-                        println!("\x1b[2J\x1b[H"); // Clear screen and move cursor home
-                        
-                        // Redraw images not yet decided (idx..displayed.len())
-                        for i in idx..displayed.len() {
-                            let (path, _) = &displayed[i];
-                            match load_and_display_image(path, scaled_display_width_chars) {
-                                Ok(_) => {
-                                    let abbrev = term::abbreviate_path(path, "", cols as usize);
-                                    println!("{}", abbrev);
-                                }
-                                Err(_) => {} // Silently skip redraw errors
-                            }
+                Key::Char('u' | 'U') => {
+                    // Undo the most recent decision, possibly from an earlier batch
+                    let Some(decision) = undo_last(&mut history) else {
+                        print!("\x07"); // Bell: nothing to undo, or the file could not be restored
+                        io::stdout().flush().unwrap();
+                        continue;
+                    };
+                    record_decision(&mut journal, Fingerprint::of(&decision.path), "undo");
+                    images.push(decision.path.clone());
+                    if idx > 0 && displayed[idx - 1].0 == decision.path {
+                        // Same batch: step back to its slot
+                        idx -= 1;
+                        decisions.pop();
+                        // Redraws since it was decided (resize, Ctrl+L, review) left it off the grid
+                        if idx < displayed.len() - layout.placements.len() {
+                            layout = redraw_undecided(&mut displayed[idx..], fallback_cell_px, fit, renderer, encoding, images.len());
                         }
-                        
-                        // Redraw image count and continue with current prompt
-                        println!("\n📸 Picked {} images out of {}", displayed.len(), images.len());
-                        continue; // Skip to next iteration of inner prompt loop
+                    } else {
+                        // Earlier batch: bring it into this one as the next image to decide
+                        displayed.insert(idx, (decision.path, ImageInfo::default()));
+                        layout = redraw_undecided(&mut displayed[idx..], fallback_cell_px, fit, renderer, encoding, images.len());
                     }
-                    
-                    // Check original char BEFORE lowercasing so we can distinguish 'i' vs 'I'
-                    match c {
-                        'I' => {
-                            // Capital [I]: show comprehensive info for all 3 images + calculations
-                            display_full_scaling_info(&displayed, cols, rows, px_width, px_height, 
-                                                     scale_factor, available_height_px, available_width_px);
-                            // Wait for keypress
-                            let _ = term::read_single_char();
-                            println!("\n");
-                            continue;
+                }
+                Key::Char(digit @ '1'..='9') => {
+                    // File into the directory bound to this key
+                    let key = digit as u8 - b'0';
+                    let Some(dest) = destinations.get(key) else {
+                        print!("\x07"); // Bell: key not bound
+                        io::stdout().flush().unwrap();
+                        continue;
+                    };
+                    if dry_run {
+                        let action = Action::WouldFile(key, dest.clone());
+                        decisions.push(action.label());
+                        history.push(Decision { path: path.clone(), action });
+                        images.retain(|p| *p != path);
+                        idx += 1;
+                        continue;
+                    }
+                    // Fingerprint first: a moved file is gone from its path
+                    let fingerprint = Fingerprint::of(&path);
+                    match destinations::file_into(&path, dest) {
+                        Ok(filed) => {
+                            let action = Action::Filed { key, filed, mode: dest.mode };
+                            decisions.push(action.label());
+                            record_decision(&mut journal, fingerprint, dest.mode.name());
+                            history.push(Decision { path: path.clone(), action });
+                            images.retain(|p| *p != path);
+                            idx += 1;
                         }
-                        'i' => {
-                            // Lowercase [i]: show info for current image only
-                            println!("\n\n📊 Image Info (current):");
-                            println!("  Terminal:           {} cols × {} rows", cols, rows);
-                            println!("  Terminal pixels:    {} × {} px", px_width, px_height);
-                            let px_per_char_h = px_height / rows as u32;
-                            let px_per_char_w = px_width / cols as u32;
-                            println!("  Pixel per char:     {} × {} px/char", px_per_char_w, px_per_char_h);
-                            println!("  Original image:     {} × {} px", info.orig_w, info.orig_h);
-                            println!("  Scaling factor:     {:.2}", info.scale_factor);
-                            println!("  Scaled image:       {} × {} px", info.scaled_w, info.scaled_h);
-                            println!("  Display in term:    35 chars × ~{} chars", 
-                                     (info.scaled_h + px_per_char_h - 1) / px_per_char_h);
-                            println!("  (press any key to continue)");
+                        Err(e) => {
+                            eprintln!("\n{} error: {}: {}", dest.mode.name(), path.display(), e);
+                            print!("\x07"); // Bell on failure
                             io::stdout().flush().unwrap();
-                            
-                            // Wait for keypress
-                            let _ = term::read_single_char();
-                            println!("\n"); // Clear and restart
-                            continue;
-                        }
-                        _ => {
-                            // Lowercase other keys for case-insensitive matching
-                            match c.to_lowercase().next() {
-                                Some('k') => {
-                                    decisions.push('k');
-                                    // Remove from collection
-                                    images.retain(|p| p != path);
-                                    break;
-                                }
-                                Some('b') => {
-                                    if macos::move_to_trash(path) {
-                                        decisions.push('b');
-                                        // Remove from collection
-                                        images.retain(|p| p != path);
-                                        break;
-                                    } else {
-                                        print!("\x07"); // Bell on failure
-                                        io::stdout().flush().unwrap();
-                                    }
-                                }
-                                Some(' ') | Some('l') => {
-                                    // Open QuickLook preview (hidden, no prompt)
-                                    macos::quicklook_preview(path);
-                                    continue;
-                                }
-                                Some('q') => {
-                                    // Quit (hidden)
-                                    term::disable_raw_mode(&original_termios).ok();
-                                    std::process::exit(0);
-                                }
-                                _ => {
-                                    print!("\x07"); // Bell on invalid input
-                                    io::stdout().flush().unwrap();
-                                }
-                            }
                         }
                     }
-                } else {
-                    break;
+                }
+                Key::Char(' ' | 'l' | 'L') => {
+                    // Open QuickLook preview (hidden, no prompt)
+                    platform::quicklook_preview(&path);
+                }
+                Key::Char('q' | 'Q') => {
+                    // Quit (hidden)
+                    println!();
+                    break 'session;
+                }
+                _ => {
+                    print!("\x07"); // Bell on invalid input
+                    io::stdout().flush().unwrap();
                 }
             }
         }
 
         // All decisions made, move to next line and ask to continue
-        println!("\n[c]ontinue, [r]estart, [q]uit: ");
+        let prompt = if defer {
+            "[c]ontinue, [C]ommit marks, [u]ndo, [r]estart, [q]uit: "
+        } else {
+            "[c]ontinue, [u]ndo, [r]estart, [q]uit: "
+        };
+        println!("\n{}", prompt);
         io::stdout().flush().unwrap();
-        
+        // The review screen clears the images, including this batch's
+        let mut reviewed = false;
+
         loop {
-            if let Ok(c) = term::read_single_char() {
-                match c.to_lowercase().next() {
-                    Some('c') => {
-                        println!();
-                        chosen = None; // Pick new 3 images
-                        break;
-                    }
-                    Some('r') => {
-                        println!("\x1b[2J\x1b[H"); // Clear screen and restart loop
-                        chosen = None; // Pick new 3 images
-                        break;
-                    }
-                    Some('q') => {
-                        println!();
-                        term::disable_raw_mode(&original_termios).ok();
-                        std::process::exit(0);
-                    }
-                    _ => {
-                        print!("\x07"); // Bell
-                        io::stdout().flush().unwrap();
+            let key = match input::read_key() {
+                Ok(key) => key,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => break 'session, // stdin closed
+            };
+            match key {
+                Key::Char('C') if defer => {
+                    review::review_marked(&mut history, &mut journal, renderer);
+                    reviewed = true;
+                    println!("{}", prompt);
+                    io::stdout().flush().unwrap();
+                }
+                Key::Char('c' | 'C') => {
+                    println!();
+                    chosen = None; // Pick a new batch
+                    break 'batch;
+                }
+                Key::Char('u' | 'U') => {
+                    // The last decision is always this batch's final slot
+                    if let Some(decision) = undo_last(&mut history) {
+                        record_decision(&mut journal, Fingerprint::of(&decision.path), "undo");
+                        images.push(decision.path);
+                        idx -= 1;
+                        decisions.pop();
+                        if reviewed || idx < displayed.len() - layout.placements.len() {
+                            // Its image is no longer on screen
+                            layout = redraw_undecided(&mut displayed[idx..], fallback_cell_px, fit, renderer, encoding, images.len());
+                        } else {
+                            println!();
+                        }
+                        continue 'batch;
                     }
+                    print!("\x07"); // Bell
+                    io::stdout().flush().unwrap();
+                }
+                Key::Char('r' | 'R') => {
+                    renderer.clear_images();
+                    println!("\x1b[2J\x1b[H"); // Clear screen and restart loop
+                    chosen = None; // Pick a new batch
+                    break 'batch;
+                }
+                Key::Char('q' | 'Q') => {
+                    println!();
+                    break 'session;
+                }
+                _ => {
+                    print!("\x07"); // Bell
+                    io::stdout().flush().unwrap();
                 }
-            } else {
-                break;
             }
         }
+        }
     }
     
+    // Nothing marked is trashed without going through the review screen
+    if defer && let review::Outcome::Cancelled = review::review_marked(&mut history, &mut journal, renderer) {
+        let pending = history.iter().filter(|d| matches!(d.action, Action::Marked)).count();
+        println!("{} marked images left untouched", pending);
+    }
+
     // Restore terminal
-    let _ = term::disable_raw_mode(&original_termios);
+    drop(terminal);
+
+    if dry_run {
+        let kept = history.iter().filter(|d| matches!(d.action, Action::Keep)).count();
+        let would_trash: Vec<PathBuf> = history
+            .iter()
+            .filter(|d| matches!(d.action, Action::WouldTrash))
+            .map(|d| d.path.clone())
+            .collect();
+        let would_file: Vec<(PathBuf, Destination)> = history
+            .iter()
+            .filter_map(|d| match &d.action {
+                Action::WouldFile(_, dest) => Some((d.path.clone(), dest.clone())),
+                _ => None,
+            })
+            .collect();
+        dry_run::print_summary(kept, &would_trash, &would_file);
+        if let Some(report) = &dry_run_report {
+            match dry_run::write_report(report, &would_trash, &would_file) {
+                Ok(()) => println!("📝 Wrote {}", report.display()),
+                Err(e) => eprintln!("Failed to write {}: {}", report.display(), e),
+            }
+        }
+    }
 }
 
 
 
-/// Pre-calculate image display height in character rows
-pub fn calc_image_height_rows(path: &Path, display_width_chars: u32, pixels_per_char_w: u32, pixels_per_char_h: u32) -> Result<u32, String> {
-    let img = image::open(path)
-        .map_err(|e| e.to_string())?;
+/// What was done with an image
+enum Action {
+    Keep,
+    /// Moved to the trash; holds the file's location inside the trash
+    Trash(PathBuf),
+    /// Marked for the trash in a dry run; nothing on disk changed
+    WouldTrash,
+    /// Marked for the trash with --defer; applied from the review screen
+    Marked,
+    /// Moved or copied into the directory bound to a number key
+    Filed { key: u8, filed: PathBuf, mode: destinations::Mode },
+    /// Would have been filed, in a dry run
+    WouldFile(u8, Destination),
+}
 
-    let (w, h) = img.dimensions();
-    let aspect_ratio = h as f32 / w as f32;
+impl Action {
+    /// Shown in the prompt slot once decided
+    fn label(&self) -> String {
+        match self {
+            Action::Keep => "k".to_string(),
+            Action::Trash(_) => "b".to_string(),
+            Action::WouldTrash => "b?".to_string(),
+            Action::Marked => "b*".to_string(),
+            Action::Filed { key, .. } => key.to_string(),
+            Action::WouldFile(key, _) => format!("{}?", key),
+        }
+    }
+}
 
-    // Display width in pixels (35 chars * pixels_per_char_w)
-    let display_width_px = display_width_chars * pixels_per_char_w;
+/// One decision, kept for the whole session so it can be undone
+struct Decision {
+    path: PathBuf,
+    action: Action,
+}
 
-    // Calculate height in pixels using aspect ratio
-    let height_px = (display_width_px as f32 * aspect_ratio) as u32;
+/// Pop the most recent decision and reverse its effect on disk
+///
+/// A trashed or moved file goes back to its original path and a copy is deleted.
+/// If that fails the decision stays on the stack and None is returned.
+fn undo_last(history: &mut Vec<Decision>) -> Option<Decision> {
+    let decision = history.pop()?;
+    let undone = match &decision.action {
+        Action::Trash(trashed) => platform::restore_from_trash(trashed, &decision.path),
+        Action::Filed { filed, mode, .. } => match destinations::unfile(&decision.path, filed, *mode) {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("\nCannot undo {}: {}: {}", mode.name(), filed.display(), e);
+                false
+            }
+        },
+        _ => true,
+    };
+    if !undone {
+        history.push(decision);
+        return None;
+    }
+    Some(decision)
+}
 
-    // Round UP to nearest character row
-    let height_rows = (height_px + pixels_per_char_h - 1) / pixels_per_char_h;
+/// Re-label this batch's decided slots after the review screen changed some decisions
+fn refresh_decisions(decisions: &mut [String], displayed: &[(PathBuf, ImageInfo)], history: &[Decision]) {
+    for (label, (path, _)) in decisions.iter_mut().zip(displayed) {
+        if let Some(decision) = history.iter().rev().find(|d| d.path == *path) {
+            *label = decision.action.label();
+        }
+    }
+}
+
+/// Append a decision to the journal, if we have one
+fn record_decision(journal: &mut Option<Journal>, fingerprint: Option<Fingerprint>, action: &str) {
+    if let (Some(journal), Some(fingerprint)) = (journal.as_mut(), fingerprint)
+        && let Err(e) = journal.record(&fingerprint, action)
+    {
+        eprintln!("\nJournal write failed: {}", e);
+    }
+}
+
+/// Command-line help, ending with the formats this build decodes
+fn usage() -> String {
+    use std::fmt::Write as _;
+
+    let mut usage = String::new();
+    let _ = writeln!(usage, "Usage: piccy-picky [OPTIONS] <path> [path2] ...");
+    let _ = writeln!(usage, "Options:");
+    let _ = writeln!(usage, "  -h, --help           Show this help and exit");
+    let _ = writeln!(usage, "  -d, --depth <N>      Search depth (default: 1)");
+    let _ = writeln!(usage, "  --batch <N>          Images shown at once, 1-{} (default: {})", MAX_BATCH_SIZE, DEFAULT_BATCH_SIZE);
+    let _ = writeln!(usage, "  --fit <STRATEGY>     Layout: {} (default: uniform)", Strategy::NAMES);
+    let _ = writeln!(usage, "  --renderer <NAME>    Image protocol: auto|{} (default: auto)", Renderer::NAMES);
+    let _ = writeln!(usage, "  --jpeg-quality <N>   JPEG quality for photographs sent to iTerm2, 1-100 (default: {})", render::DEFAULT_JPEG_QUALITY);
+    let _ = writeln!(usage, "  --max-payload <SIZE> Largest image to send, as written to the terminal, e.g. 300K or 2M; quality, then resolution give way");
+    let _ = writeln!(usage, "  --cell-size <WxH>    Cell size in pixels if the terminal cannot report it");
+    let _ = writeln!(usage, "  --hidpi <SCALE>      Send iTerm2/kitty images at SCALE× the reported pixels, e.g. 2 for Retina (default: 1)");
+    let _ = writeln!(usage, "  --resume             Skip images already decided in earlier sessions");
+    let _ = writeln!(usage, "  --move-to [N=]<DIR>  Key N (1-9, default next free) moves the image into DIR");
+    let _ = writeln!(usage, "  --copy-to [N=]<DIR>  Key N (1-9, default next free) copies the image into DIR");
+    let _ = writeln!(usage, "  --defer              'b' only marks; review and trash the marks with 'C' or on exit");
+    let _ = writeln!(usage, "  --dry-run            Only record what would be trashed; never touch the files");
+    let _ = writeln!(usage, "  --dry-run-report <FILE>  With --dry-run, write the recorded actions as a script (or JSON if FILE ends in .json)");
+    let _ = writeln!(usage, "  --test-search        Test file search only (print results and exit)");
+    let _ = writeln!(usage, "Formats: {}", formats::names());
+    usage
+}
+
+/// Parse a `--max-payload` value: bytes, or with a K/M suffix (decimal, like `term::format_bytes`)
+fn parse_size(size: &str) -> Option<usize> {
+    let upper = size.to_ascii_uppercase();
+    let digits = upper.strip_suffix('B').unwrap_or(&upper);
+    let (digits, multiplier) = match digits.strip_suffix('K') {
+        Some(digits) => (digits, 1_000),
+        None => match digits.strip_suffix('M') {
+            Some(digits) => (digits, 1_000_000),
+            None => (digits, 1),
+        },
+    };
+    let value: f64 = digits.trim().parse().ok()?;
+    let bytes = (value * multiplier as f64) as usize;
+    (bytes > 0).then_some(bytes)
+}
+
+/// Parse a `--cell-size` value like "8x16"
+fn parse_cell_size(size: &str) -> Option<(u32, u32)> {
+    let (w, h) = size.split_once('x')?;
+    let (w, h) = (w.parse().ok()?, h.parse().ok()?);
+    (w > 0 && h > 0).then_some((w, h))
+}
 
-    Ok(height_rows)
+/// Up to `count` random images not already in `exclude`
+fn pick_batch(images: &[PathBuf], exclude: &[PathBuf], count: usize) -> Vec<PathBuf> {
+    let candidates: Vec<&PathBuf> = images.iter().filter(|p| !exclude.contains(p)).collect();
+    candidates
+        .choose_multiple(&mut rand::thread_rng(), count)
+        .map(|p| (*p).clone())
+        .collect()
 }
 
+/// Current terminal size in cells and pixels, with pixels derived from `fallback_cell_px` if unknown
+fn measure_terminal(fallback_cell_px: (u32, u32)) -> Terminal {
+    // Terminal dimensions are our single source of truth for layout calculations
+    let (cols, rows) = term::get_terminal_size();           // Character grid dimensions
+    let (px_width, px_height) = term::get_terminal_pixel_size() // Pixel dimensions of terminal
+        .unwrap_or((cols as u32 * fallback_cell_px.0, rows as u32 * fallback_cell_px.1));
+    Terminal { cols, rows, px_width, px_height }
+}
+
+/// Measure the terminal and lay out `paths` with the given strategy
+fn compute_layout(paths: &[PathBuf], fallback_cell_px: (u32, u32), fit: Strategy) -> Layout {
+    let terminal = measure_terminal(fallback_cell_px);
+    let cols = terminal.cols;
+    let dims: Vec<(u32, u32)> = paths
+        .iter()
+        .map(|path| {
+            image_dimensions(path).unwrap_or_else(|e| {
+                let abbrev = term::abbreviate_path(path, "", cols as usize);
+                eprintln!("Failed to read size of {}: {}", abbrev, e);
+                (1, 1) // Square placeholder; the image is reported again when drawn
+            })
+        })
+        .collect();
+    layout::compute(terminal, &dims, fit)
+}
+
+/// Clear the screen and lay out and redraw the images still awaiting a decision, refreshing their info
+fn redraw_undecided(undecided: &mut [(PathBuf, ImageInfo)], fallback_cell_px: (u32, u32), fit: Strategy, renderer: Renderer, encoding: Encoding, remaining: usize) -> Layout {
+    let paths: Vec<PathBuf> = undecided.iter().map(|(p, _)| p.clone()).collect();
+    let layout = compute_layout(&paths, fallback_cell_px, fit);
+    for ((_, info), result) in undecided.iter_mut().zip(draw_batch(&paths, &layout, renderer, encoding)) {
+        // Silently skip redraw errors
+        if let Ok(new_info) = result {
+            *info = new_info;
+        }
+    }
+
+    // Redraw image count and continue with current prompt
+    println!("📸 Picked {} images out of {}", undecided.len(), remaining);
+    layout
+}
+
+/// Clear the screen and draw `paths` at their grid places, each with its path underneath
+///
+/// Leaves the cursor on the line below the grid. Results are in the order of `paths`.
+fn draw_batch(paths: &[PathBuf], layout: &Layout, renderer: Renderer, encoding: Encoding) -> Vec<Result<ImageInfo, String>> {
+    renderer.clear_images();
+    print!("\x1b[2J\x1b[H"); // Clear screen and move cursor home
+
+    let mut results = Vec::with_capacity(paths.len());
+    for (path, place) in paths.iter().zip(&layout.placements) {
+        // Cursor positions are 1-based
+        print!("\x1b[{};{}H", place.row + 1, place.col + 1);
+        let result = load_and_display_image(path, place.width_chars, layout.cell_px(), renderer, encoding);
+        if result.is_ok() {
+            let abbrev = term::abbreviate_path(path, "", place.width_chars as usize);
+            print!("\x1b[{};{}H{}", place.row + place.height_rows + 1, place.col + 1, abbrev);
+        }
+        results.push(result);
+    }
+    print!("\x1b[{};1H", layout.height_rows + 1);
+    io::stdout().flush().unwrap();
+    results
+}
+
+/// Width and height of an image in pixels, read from the header unless already decoded
+fn image_dimensions(path: &Path) -> Result<(u32, u32), String> {
+    cache::dimensions(path)
+}
+
+#[derive(Default)]
 pub struct ImageInfo {
     pub orig_w: u32,
     pub orig_h: u32,
     pub scaled_w: u32,
     pub scaled_h: u32,
     pub scale_factor: f32,
+    /// What was sent to the terminal, e.g. "jpeg q85, 42.1 KB"
+    pub payload: String,
+}
+
+/// Show what we know about one image: file, pixels, how it was sent, and its EXIF
+///
+/// `place` is the image's slot in `layout`, if it has one.
+fn display_image_info(path: &Path, info: &ImageInfo, layout: &Layout, place: Option<&layout::Placement>) {
+    let details = metadata::Details::of(path);
+    let (px_per_char_w, px_per_char_h) = layout.cell_px();
+
+    println!("\n\n📊 Image Info (current):");
+    println!("  File:               {}", term::abbreviate_path(path, "", 60));
+    if let Some(size) = details.size {
+        println!("  File size:          {}", term::format_bytes(size));
+    }
+    if let Some(modified) = details.modified.and_then(term::format_local_time) {
+        println!("  Modified:           {}", modified);
+    }
+    let colour = cache::load(path).ok().map(|img| metadata::describe_colour(img.image.color()));
+    let format: Vec<String> = details.format.into_iter().chain(colour).collect();
+    if !format.is_empty() {
+        println!("  Format:             {}", format.join(", "));
+    }
+    println!("  Terminal:           {} cols × {} rows", layout.terminal.cols, layout.terminal.rows);
+    println!("  Terminal pixels:    {} × {} px", layout.terminal.px_width, layout.terminal.px_height);
+    println!("  Pixel per char:     {} × {} px/char", px_per_char_w, px_per_char_h);
+    println!("  Original image:     {} × {} px", info.orig_w, info.orig_h);
+    println!("  Scaling factor:     {:.2}", info.scale_factor);
+    println!("  Scaled image:       {} × {} px", info.scaled_w, info.scaled_h);
+    println!("  Sent as:            {}", info.payload);
+    if let Some(place) = place {
+        println!("  Display in term:    {} chars × {} rows", place.width_chars, place.height_rows);
+    }
+    if !details.exif.is_empty() {
+        println!("\n📷 EXIF:");
+        for (label, value) in &details.exif {
+            println!("  {:<20}{}", format!("{}:", label), value);
+        }
+    }
+    println!("  (press any key to continue)");
+    io::stdout().flush().unwrap();
 }
 
-/// Display comprehensive scaling info for all 3 images + calculations
-/// Shows original sizes, available space, scale factors, and final display dimensions
-fn display_full_scaling_info(
-    displayed: &[(PathBuf, ImageInfo)],
-    cols: u16,
-    rows: u16,
-    px_width: u32,
-    px_height: u32,
-    scale_factor: f32,
-    available_height_px: u32,
-    available_width_px: u32,
-) {
+/// Display comprehensive scaling info for the whole batch + calculations
+///
+/// `displayed` must be the images `layout` was computed for, in the same order.
+fn display_full_scaling_info(displayed: &[(PathBuf, ImageInfo)], layout: &Layout, renderer: Renderer) {
+    let Terminal { cols, rows, px_width, px_height } = layout.terminal;
+    let (px_per_char_w, px_per_char_h) = layout.cell_px();
+    let (area_cols, area_rows) = layout.terminal.image_area();
     println!("\n\n╔════════════════════════════════════════════════════════════════════╗");
     println!("║                    COMPREHENSIVE SCALING INFO [I]                    ║");
     println!("╚════════════════════════════════════════════════════════════════════╝");
@@ -445,135 +937,99 @@ fn display_full_scaling_info(
     println!("\n📱 TERMINAL:");
     println!("  Character grid:     {} cols × {} rows", cols, rows);
     println!("  Pixel dimensions:   {} × {} px", px_width, px_height);
-    let px_per_char_w = px_width / cols.max(1) as u32;
-    let px_per_char_h = px_height / rows.max(1) as u32;
     println!("  Pixels per char:    {} × {} px/char (w × h)", px_per_char_w, px_per_char_h);
+    println!("  Renderer:           {}", renderer.name());
     
     // Available space
     println!("\n📏 AVAILABLE SPACE:");
-    let ui_rows = 5u32;
-    let ui_height_px = ui_rows * px_per_char_h;
-    println!("  UI height:          {} rows = {} px", ui_rows, ui_height_px);
-    println!("  Available height:   {} px ({} rows)", available_height_px, 
-             available_height_px / px_per_char_h.max(1));
-    let width_margin_cols = 2u32;
-    let available_width_cols = (cols as u32).saturating_sub(width_margin_cols);
-    println!("  Available width:    {} px ({} cols, margin {})", 
-             available_width_px, available_width_cols, width_margin_cols);
+    println!("  UI height:          {} rows = {} px", layout::UI_ROWS, layout::UI_ROWS * px_per_char_h);
+    println!("  Image area:         {} cols × {} rows = {} × {} px (margin {} cols)",
+             area_cols, area_rows, area_cols * px_per_char_w, area_rows * px_per_char_h, layout::WIDTH_MARGIN_COLS);
     
-    // Per-image breakdown
-    println!("\n🖼️  IMAGES (3 shown):");
-    println!("  Global scale factor: {:.3}", scale_factor);
+    // Per-image breakdown, straight from the layout's placements
+    println!("\n🖼️  IMAGES ({} shown):", displayed.len());
+    println!("  Fit strategy:       {}", layout.strategy.name());
+    println!("  Grid:               {} columns × {} rows", layout.columns, layout.rows);
     
-    for (idx, (path, info)) in displayed.iter().enumerate() {
+    for (idx, ((path, info), place)) in displayed.iter().zip(&layout.placements).enumerate() {
         let abbrev = term::abbreviate_path(path, "", 50);
+        let display_w = place.width_chars * px_per_char_w;
         println!("\n  Image {}:", idx + 1);
         println!("    File:             {}", abbrev);
         println!("    Original:         {} × {} px", info.orig_w, info.orig_h);
-        println!("    Original aspect:  {:.3}:1", info.orig_h as f32 / info.orig_w as f32);
-        println!("    After scaling:    {} × {} px", info.scaled_w, info.scaled_h);
-        println!("    Scaling applied:  {:.3}× (multiply original by this)", info.scale_factor);
-        
-        // Calculate what this image would be at the available width
-        let theoretical_w = available_width_px;
-        let theoretical_h = (theoretical_w as f32 * info.orig_h as f32 / info.orig_w as f32) as u32;
-        println!("    If scaled to available width: {} × {} px", theoretical_w, theoretical_h);
-        
-        // Display dimensions accounting for global scale factor
-        // When we pass scaled_display_width_chars to iTerm2, it scales height proportionally
-        let scaled_display_h = (theoretical_h as f32 * scale_factor) as u32;
-        let display_rows = (scaled_display_h + px_per_char_h - 1) / px_per_char_h;
-        println!("    Actual display (after scale): {} × {} px = ~{} chars tall", theoretical_w, scaled_display_h, display_rows);
+        println!("    Original aspect:  {:.3}:1", info.orig_h as f32 / info.orig_w.max(1) as f32);
+        println!("    Position:         row {}, col {}", place.row + 1, place.col + 1);
+        println!("    Display:          {} chars × {} rows = {} × {} px",
+                 place.width_chars, place.height_rows, display_w, place.height_rows * px_per_char_h);
+        println!("    Scale vs original: {:.3}×", display_w as f32 / info.orig_w.max(1) as f32);
+        println!("    Sent as:          {} × {} px, {}", info.scaled_w, info.scaled_h, info.payload);
     }
     
     // Summary validation
     println!("\n✅ VALIDATION:");
-    // Calculate actual display heights (after applying global scale factor)
-    let mut total_actual_height_px = 0u32;
-    for (_, info) in displayed.iter() {
-        let theoretical_h = (available_width_px as f32 * info.orig_h as f32 / info.orig_w as f32) as u32;
-        let scaled_h = (theoretical_h as f32 * scale_factor) as u32;
-        total_actual_height_px += scaled_h;
-    }
-    let total_needed_px = total_actual_height_px;
-    
-    println!("  Total image heights (after scale): {} px", total_actual_height_px);
-    println!("  Total needed:        {} px", total_needed_px);
-    println!("  Available:           {} px", available_height_px);
-    
-    if total_needed_px <= available_height_px {
-        println!("  ✓ FITS with {} px to spare ({:.1}% utilized)", 
-                 available_height_px - total_needed_px,
-                 (total_needed_px as f32 / available_height_px as f32) * 100.0);
+    println!("  Rows used (with path lines): {}", layout.height_rows);
+    println!("  Available:                   {}", area_rows);
+    if layout.fits() {
+        println!("  ✓ FITS with {} rows to spare ({:.1}% utilized)",
+                 area_rows - layout.height_rows,
+                 (layout.height_rows as f32 / area_rows.max(1) as f32) * 100.0);
     } else {
-        let overage = total_needed_px - available_height_px;
-        println!("  ✗ OVERFLOW by {} px ({:.1}% over budget)", 
-                 overage,
-                 (overage as f32 / available_height_px as f32) * 100.0);
+        let overage = layout.height_rows.saturating_sub(area_rows);
+        println!("  ✗ OVERFLOW by {} rows (terminal too small for this batch)", overage);
     }
     
     println!("\n  (press any key to continue)");
     io::stdout().flush().unwrap();
 }
 
-fn load_and_display_image(path: &Path, display_width_chars: u32) -> Result<ImageInfo, String> {
-    // CRITICAL: Never scale twice. 
-    // display_width_chars is ALREADY scaled by layout_scale (done in main loop).
-    // We now just load the image and tell iTerm2 what width to display it at.
-    // iTerm2 handles all the scaling to fit that width while preserving aspect ratio.
+fn load_and_display_image(path: &Path, display_width_chars: u32, cell_px: (u32, u32), renderer: Renderer, encoding: Encoding) -> Result<ImageInfo, String> {
+    // CRITICAL: Never let the terminal scale.
+    // display_width_chars is ALREADY the layout's width for this image.
+    // The image is resampled to the pixels that width covers, and the terminal
+    // is told the width in cells so it places it without scaling further.
     //
     // Flow:
-    // 1. Load image at original size (reduce only if >4000px for file size)
-    // 2. Encode to PNG
-    // 3. Tell iTerm2 the display_width_chars (already scaled down if needed)
-    // 4. iTerm2 scales image to fit that width, maintaining aspect ratio
-    // Result: single scaling pass, no overflow
-    
-    let img = image::open(path)
-        .map_err(|e| e.to_string())?;
+    // 1. Decode once per file; redraws hit the cache. Files over 4000px are
+    //    pre-shrunk here to bound memory, which is the only other resample
+    // 2. Resample to the slot's pixel box (times --hidpi) and encode, also cached
+    // 3. Tell the terminal the display_width_chars
+    // Result: one resample to the final size, no overflow, and no more data sent than is shown
+    let img = cache::load(path)?;
+    let payload = renderer.display(&img, display_width_chars, cell_px, encoding)?;
 
-    let (w, h) = img.dimensions();
-    
-    // Only apply encode_scale for truly massive images (>4000px) to reduce file size
-    // Do NOT apply layout_scale to the image—let iTerm2 handle that via the width parameter
-    let max_dim = 4000u32;
-    let encode_scale = if w > max_dim || h > max_dim {
-        (max_dim as f32 / w.max(h) as f32).min(1.0)
-    } else {
-        1.0
+    let (w, h) = img.orig_dims;
+    let (scaled_w, scaled_h) = payload.dims;
+    let format = match payload.quality {
+        Some(quality) => format!("{} q{}", payload.format, quality),
+        None => payload.format.to_string(),
     };
-
-    let final_w = (w as f32 * encode_scale) as u32;
-    let final_h = (h as f32 * encode_scale) as u32;
-
-    let img_to_encode = if encode_scale < 1.0 {
-        let scaled_w = (w as f32 * encode_scale) as u32;
-        let scaled_h = (h as f32 * encode_scale) as u32;
-        img.resize_exact(scaled_w, scaled_h, image::imageops::FilterType::Lanczos3)
-    } else {
-        img
-    };
-
-    // Encode to PNG and display
-    let mut png_data = Vec::new();
-    let mut cursor = Cursor::new(&mut png_data);
-    img_to_encode.write_to(&mut cursor, image::ImageFormat::Png)
-        .map_err(|e| e.to_string())?;
-
-    use base64::Engine;
-    let encoded = base64::engine::general_purpose::STANDARD.encode(&png_data);
-    let size = encoded.len();
-    
-    // Pass the display_width to iTerm2 - this tells it how wide to make the image
-    // iTerm2 will scale the image to fit this width and maintain aspect ratio
-    println!("\x1b]1337;File=name=image.png;size={};inline=1;width={}c;base64:{}\x07", 
-             size, display_width_chars, encoded);
-
     Ok(ImageInfo {
         orig_w: w,
         orig_h: h,
-        scaled_w: final_w,
-        scaled_h: final_h,
-        scale_factor: encode_scale,  // Only encode_scale, not layout_scale (iTerm2 handles that)
+        scaled_w,
+        scaled_h,
+        scale_factor: scaled_w as f32 / w.max(1) as f32,
+        payload: format!("{}, {}", format, term::format_bytes(payload.data.len() as u64)),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_take_decimal_suffixes() {
+        assert_eq!(parse_size("500000"), Some(500_000));
+        assert_eq!(parse_size("300K"), Some(300_000));
+        assert_eq!(parse_size("300kb"), Some(300_000));
+        assert_eq!(parse_size("2M"), Some(2_000_000));
+        assert_eq!(parse_size("1.5MB"), Some(1_500_000));
+    }
+
+    #[test]
+    fn sizes_must_be_positive_numbers() {
+        for size in ["", "K", "0", "-5K", "0.0001K", "lots", "2G"] {
+            assert_eq!(parse_size(size), None, "{:?}", size);
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use image::{ColorType, DynamicImage};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::formats;
+
+// EXIF, read with kamadak-exif from JPEG, TIFF, PNG, WebP and HEIF containers.
+// Files without EXIF (or with a damaged block) simply have none; nothing here fails.
+
+/// How the stored pixels must be turned to display upright (EXIF Orientation, 1-8)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Orientation(u32);
+
+impl Orientation {
+    /// The file's orientation tag, or upright if it has none
+    pub fn of(path: &Path) -> Orientation {
+        read_exif(path)
+            .and_then(|exif| exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)?.value.get_uint(0))
+            .filter(|value| (1..=8).contains(value))
+            .map_or(Orientation::default(), Orientation)
+    }
+
+    /// True for the four orientations that swap width and height (5-8)
+    fn swaps_axes(self) -> bool {
+        (5..=8).contains(&self.0)
+    }
+
+    /// Display size of an image stored as `dims`
+    pub fn apply_to_dims(self, dims: (u32, u32)) -> (u32, u32) {
+        if self.swaps_axes() { (dims.1, dims.0) } else { dims }
+    }
+
+    /// Turn decoded pixels upright
+    pub fn apply(self, img: DynamicImage) -> DynamicImage {
+        match self.0 {
+            2 => img.fliph(),
+            3 => img.rotate180(),
+            4 => img.flipv(),
+            5 => img.rotate90().fliph(), // Transpose
+            6 => img.rotate90(),
+            7 => img.rotate270().fliph(), // Transverse
+            8 => img.rotate270(),
+            _ => img, // 1 (or unset): already upright
+        }
+    }
+}
+
+/// What the info panel shows about a file besides its pixels
+pub struct Details {
+    pub size: Option<u64>,
+    pub modified: Option<SystemTime>,
+    /// Container format from the file's signature, e.g. "JPEG"
+    pub format: Option<String>,
+    /// Labelled EXIF values that are present, in display order
+    pub exif: Vec<(&'static str, String)>,
+}
+
+impl Details {
+    pub fn of(path: &Path) -> Details {
+        let meta = fs::metadata(path).ok();
+        let format = image::io::Reader::open(path)
+            .and_then(|reader| reader.with_guessed_format())
+            .ok()
+            .and_then(|reader| reader.format())
+            .map(|format| format!("{:?}", format).to_uppercase())
+            // Formats decoded outside the image crate (HEIF) aren't sniffed
+            .or_else(|| formats::for_path(path).map(|format| format.name.to_string()));
+        Details {
+            size: meta.as_ref().map(|m| m.len()),
+            modified: meta.and_then(|m| m.modified().ok()),
+            format,
+            exif: read_exif(path).map(|exif| exif_summary(&exif)).unwrap_or_default(),
+        }
+    }
+}
+
+/// Channels and bit depth, e.g. "RGBA, 8-bit"
+pub fn describe_colour(colour: ColorType) -> String {
+    let channels = match (colour.channel_count(), colour.has_alpha()) {
+        (1, _) => "greyscale",
+        (2, _) => "greyscale + alpha",
+        (3, _) => "RGB",
+        (_, true) => "RGBA",
+        _ => "other",
+    };
+    let bits = colour.bits_per_pixel() / colour.channel_count().max(1) as u16;
+    match colour {
+        ColorType::Rgb32F | ColorType::Rgba32F => format!("{}, 32-bit float", channels),
+        _ => format!("{}, {}-bit", channels, bits),
+    }
+}
+
+/// The fields we decide by: when, with what, how exposed, and where
+fn exif_summary(exif: &exif::Exif) -> Vec<(&'static str, String)> {
+    use exif::Tag;
+
+    let field = |tag| exif.get_field(tag, exif::In::PRIMARY);
+    let text = |tag| field(tag).and_then(|f| ascii(&f.value));
+    let with_unit = |tag| field(tag).map(|f| f.display_value().with_unit(exif).to_string());
+
+    let mut summary = Vec::new();
+    if let Some(f) = field(Tag::DateTimeOriginal).or_else(|| field(Tag::DateTime)) {
+        summary.push(("Captured", f.display_value().to_string()));
+    }
+    let camera = match (text(Tag::Make), text(Tag::Model)) {
+        // Models often repeat the make ("Canon" + "Canon EOS R6")
+        (Some(make), Some(model)) if model.starts_with(&make) => Some(model),
+        (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+        (make, model) => make.or(model),
+    };
+    if let Some(camera) = camera {
+        summary.push(("Camera", camera));
+    }
+    if let Some(lens) = text(Tag::LensModel) {
+        summary.push(("Lens", lens));
+    }
+    let exposure: Vec<String> = [Tag::ExposureTime, Tag::FNumber]
+        .into_iter()
+        .filter_map(with_unit)
+        .collect();
+    if !exposure.is_empty() {
+        summary.push(("Exposure", exposure.join(" at ")));
+    }
+    if let Some(f) = field(Tag::PhotographicSensitivity) {
+        summary.push(("ISO", f.display_value().to_string()));
+    }
+    if let Some(focal) = with_unit(Tag::FocalLength) {
+        let equivalent = field(Tag::FocalLengthIn35mmFilm).and_then(|f| f.value.get_uint(0)).filter(|&mm| mm > 0);
+        summary.push(("Focal length", match equivalent {
+            Some(mm) => format!("{} ({} mm full-frame equivalent)", focal, mm),
+            None => focal,
+        }));
+    }
+    if let (Some(lat), Some(lon)) = (
+        gps_coordinate(exif, Tag::GPSLatitude, Tag::GPSLatitudeRef),
+        gps_coordinate(exif, Tag::GPSLongitude, Tag::GPSLongitudeRef),
+    ) {
+        let (ns, ew) = (if lat < 0.0 { 'S' } else { 'N' }, if lon < 0.0 { 'W' } else { 'E' });
+        summary.push(("GPS", format!("{:.5}° {}, {:.5}° {}", lat.abs(), ns, lon.abs(), ew)));
+    }
+    summary
+}
+
+/// Signed decimal degrees from degrees/minutes/seconds and an N/S or E/W reference
+fn gps_coordinate(exif: &exif::Exif, tag: exif::Tag, reference: exif::Tag) -> Option<f64> {
+    let exif::Value::Rational(dms) = &exif.get_field(tag, exif::In::PRIMARY)?.value else {
+        return None;
+    };
+    // A partial coordinate would show as somewhere near 0°, so it's as good as none
+    let [d, m, s] = dms.get(..3)? else {
+        return None;
+    };
+    let degrees = d.to_f64() + m.to_f64() / 60.0 + s.to_f64() / 3600.0;
+    if !degrees.is_finite() {
+        return None;
+    }
+    let negative = exif
+        .get_field(reference, exif::In::PRIMARY)
+        .and_then(|f| ascii(&f.value))
+        .is_some_and(|r| r == "S" || r == "W");
+    Some(if negative { -degrees } else { degrees })
+}
+
+/// First string of an ASCII field, trimmed; None if empty
+fn ascii(value: &exif::Value) -> Option<String> {
+    let exif::Value::Ascii(strings) = value else {
+        return None;
+    };
+    let text = String::from_utf8_lossy(strings.first()?).trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+fn read_exif(path: &Path) -> Option<exif::Exif> {
+    let file = File::open(path).ok()?;
+    exif::Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use exif::{Field, Rational, Tag, Value};
+    use image::{Rgb, RgbImage};
+    use std::io::Cursor;
+
+    const STORED: (u32, u32) = (3, 2);
+
+    /// Every pixel encodes its own stored position, so any mix-up shows
+    fn stored() -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(STORED.0, STORED.1, |x, y| Rgb([x as u8, y as u8, 0])))
+    }
+
+    /// Stored position shown at display position (x, y), per the EXIF definition of each value
+    fn source(orientation: u32, (x, y): (u32, u32)) -> (u32, u32) {
+        let (w, h) = STORED;
+        match orientation {
+            2 => (w - 1 - x, y),
+            3 => (w - 1 - x, h - 1 - y),
+            4 => (x, h - 1 - y),
+            5 => (y, x),
+            6 => (y, h - 1 - x),
+            7 => (w - 1 - y, h - 1 - x),
+            8 => (w - 1 - y, x),
+            _ => (x, y),
+        }
+    }
+
+    #[test]
+    fn every_orientation_turns_pixels_upright() {
+        for value in 1..=8 {
+            let upright = Orientation(value).apply(stored()).to_rgb8();
+            let expected_dims = if value >= 5 { (STORED.1, STORED.0) } else { STORED };
+            assert_eq!(upright.dimensions(), expected_dims, "orientation {}", value);
+            for (x, y, pixel) in upright.enumerate_pixels() {
+                let (sx, sy) = source(value, (x, y));
+                assert_eq!(pixel.0, [sx as u8, sy as u8, 0], "orientation {} at ({}, {})", value, x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn dims_swap_for_the_rotated_orientations() {
+        for value in 1..=8 {
+            let expected = if value >= 5 { (2, 3) } else { (3, 2) };
+            assert_eq!(Orientation(value).apply_to_dims((3, 2)), expected, "orientation {}", value);
+        }
+    }
+
+    #[test]
+    fn unknown_values_leave_the_image_as_stored() {
+        for value in [0, 9, 255] {
+            let orientation = Orientation(value);
+            assert_eq!(orientation.apply_to_dims((3, 2)), (3, 2));
+            assert_eq!(orientation.apply(stored()).to_rgb8(), stored().to_rgb8());
+        }
+    }
+
+    /// EXIF as parsed from a TIFF block holding `fields`
+    fn exif_with(fields: &[Field]) -> exif::Exif {
+        let mut writer = exif::experimental::Writer::new();
+        for field in fields {
+            writer.push_field(field);
+        }
+        let mut tiff = Cursor::new(Vec::new());
+        writer.write(&mut tiff, false).unwrap();
+        exif::Reader::new().read_raw(tiff.into_inner()).unwrap()
+    }
+
+    fn field(tag: Tag, value: Value) -> Field {
+        Field { tag, ifd_num: exif::In::PRIMARY, value }
+    }
+
+    fn ascii(text: &str) -> Value {
+        Value::Ascii(vec![text.as_bytes().to_vec()])
+    }
+
+    fn rationals(parts: &[(u32, u32)]) -> Value {
+        Value::Rational(parts.iter().map(|&(num, denom)| Rational { num, denom }).collect())
+    }
+
+    #[test]
+    fn summary_lists_the_fields_present_in_display_order() {
+        let exif = exif_with(&[
+            field(Tag::Make, ascii("Canon")),
+            field(Tag::Model, ascii("Canon EOS R6")),
+            field(Tag::DateTimeOriginal, ascii("2024:06:01 14:03:27")),
+            field(Tag::ExposureTime, rationals(&[(1, 250)])),
+            field(Tag::FNumber, rationals(&[(28, 10)])),
+            field(Tag::PhotographicSensitivity, Value::Short(vec![400])),
+            field(Tag::FocalLength, rationals(&[(50, 1)])),
+            field(Tag::FocalLengthIn35mmFilm, Value::Short(vec![80])),
+        ]);
+        let summary = exif_summary(&exif);
+        let labels: Vec<&str> = summary.iter().map(|(label, _)| *label).collect();
+        assert_eq!(labels, ["Captured", "Camera", "Exposure", "ISO", "Focal length"]);
+        assert_eq!(summary[0].1, "2024-06-01 14:03:27");
+        assert_eq!(summary[1].1, "Canon EOS R6");
+        assert_eq!(summary[2].1, "1/250 s at f/2.8");
+        assert_eq!(summary[3].1, "400");
+        assert_eq!(summary[4].1, "50 mm (80 mm full-frame equivalent)");
+    }
+
+    #[test]
+    fn camera_joins_make_and_model_unless_the_model_repeats_it() {
+        let exif = exif_with(&[field(Tag::Make, ascii("FUJIFILM")), field(Tag::Model, ascii("X-T4"))]);
+        assert_eq!(exif_summary(&exif), [("Camera", "FUJIFILM X-T4".to_string())]);
+    }
+
+    #[test]
+    fn gps_coordinates_are_signed_by_their_reference() {
+        let exif = exif_with(&[
+            field(Tag::GPSLatitude, rationals(&[(33, 1), (51, 1), (3546, 100)])),
+            field(Tag::GPSLatitudeRef, ascii("S")),
+            field(Tag::GPSLongitude, rationals(&[(151, 1), (12, 1), (3, 1)])),
+            field(Tag::GPSLongitudeRef, ascii("E")),
+        ]);
+        let lat = gps_coordinate(&exif, Tag::GPSLatitude, Tag::GPSLatitudeRef).unwrap();
+        assert!((lat + 33.85985).abs() < 1e-5, "{}", lat);
+        assert_eq!(exif_summary(&exif), [("GPS", "33.85985° S, 151.20083° E".to_string())]);
+    }
+
+    #[test]
+    fn incomplete_gps_coordinates_are_left_out() {
+        for parts in [&[][..], &[(33, 1)], &[(33, 1), (51, 1)], &[(33, 1), (51, 1), (1, 0)]] {
+            let exif = exif_with(&[
+                field(Tag::GPSLatitude, rationals(parts)),
+                field(Tag::GPSLongitude, rationals(&[(151, 1), (12, 1), (3, 1)])),
+            ]);
+            assert_eq!(gps_coordinate(&exif, Tag::GPSLatitude, Tag::GPSLatitudeRef), None, "{:?}", parts);
+            assert!(exif_summary(&exif).is_empty());
+        }
+    }
+
+    #[test]
+    fn colour_types_name_channels_and_depth() {
+        assert_eq!(describe_colour(ColorType::L8), "greyscale, 8-bit");
+        assert_eq!(describe_colour(ColorType::La16), "greyscale + alpha, 16-bit");
+        assert_eq!(describe_colour(ColorType::Rgb8), "RGB, 8-bit");
+        assert_eq!(describe_colour(ColorType::Rgba16), "RGBA, 16-bit");
+        assert_eq!(describe_colour(ColorType::Rgb32F), "RGB, 32-bit float");
+        assert_eq!(describe_colour(ColorType::Rgba32F), "RGBA, 32-bit float");
+    }
+}
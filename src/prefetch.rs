@@ -0,0 +1,50 @@
+use std::path::PathBuf;
+use std::thread::{self, JoinHandle};
+
+use crate::layout::{self, Strategy, Terminal};
+use crate::render::{Encoding, Renderer};
+use crate::{cache, term};
+
+// While the user decides on one batch, the next is already picked and a worker thread
+// decodes and encodes it into the image cache, so continuing only has to print it.
+
+/// The next batch, being prepared in the background
+pub struct Prefetch {
+    batch: Vec<PathBuf>,
+    worker: JoinHandle<()>,
+}
+
+impl Prefetch {
+    /// Start decoding `batch` and encoding it for `renderer` at the size it would be laid out on `terminal`
+    ///
+    /// The caller measures the terminal, since a failed measurement exits the process
+    /// and that must not happen from the worker.
+    pub fn start(batch: Vec<PathBuf>, terminal: Terminal, renderer: Renderer, encoding: Encoding, fit: Strategy) -> Prefetch {
+        let paths = batch.clone();
+        // Resizes must interrupt the main thread's key read, not land on the worker
+        let worker = term::without_resize_signal(|| thread::spawn(move || {
+            // Errors are left for the main thread to report when it draws the batch
+            let images: Vec<_> = paths.iter().map(|path| cache::load(path).ok()).collect();
+            let dims: Vec<(u32, u32)> = images
+                .iter()
+                .map(|img| img.as_ref().map_or((1, 1), |img| img.orig_dims))
+                .collect();
+            let layout = layout::compute(terminal, &dims, fit);
+            for (img, place) in images.iter().zip(&layout.placements) {
+                if let Some(img) = img {
+                    let _ = renderer.prepare(img, place.width_chars, layout.cell_px(), encoding);
+                }
+            }
+        }));
+        Prefetch { batch, worker }
+    }
+
+    /// The prefetched batch, once the worker is done with it
+    ///
+    /// Waiting means the main thread never decodes an image the worker is still on.
+    /// If the worker panicked, the main thread simply decodes the batch itself.
+    pub fn finish(self) -> Vec<PathBuf> {
+        let _ = self.worker.join();
+        self.batch
+    }
+}
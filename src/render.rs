@@ -0,0 +1,351 @@
+use base64::Engine;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::io::{self, Cursor, Write};
+use std::sync::Arc;
+
+use crate::cache::CachedImage;
+use crate::{blocks, kitty, sixel};
+
+/// Terminal image protocol used to draw images inline
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Renderer {
+    /// iTerm2 inline images (`OSC 1337;File=`), also understood by WezTerm
+    Iterm2,
+    /// kitty graphics protocol (chunked APC transmission), also kitty-compatible terminals
+    Kitty,
+    /// DEC Sixel graphics (foot, mlterm, xterm, Windows Terminal), palette-quantized
+    Sixel,
+    /// Unicode half blocks with 24-bit or 256-colour SGR, works in any terminal
+    Blocks,
+}
+
+/// Quality used for photographs unless --jpeg-quality says otherwise
+pub const DEFAULT_JPEG_QUALITY: u8 = 85;
+
+/// --max-payload lowers JPEG quality down to this before it reduces resolution
+const MIN_BUDGET_JPEG_QUALITY: u8 = 40;
+
+/// Quality is lowered in steps of this much
+const BUDGET_JPEG_QUALITY_STEP: u8 = 15;
+
+/// --max-payload never shrinks an image's longer side below this many pixels
+const MIN_BUDGET_DIMENSION: u32 = 64;
+
+/// An image with at least this many distinct colours is treated as a photograph
+const PHOTO_MIN_COLOURS: usize = 1024;
+
+/// How images are encoded for the terminal, from the command line
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Encoding {
+    /// Screen pixels per terminal-reported pixel for iTerm2/kitty (e.g. 2 when a
+    /// Retina terminal reports its size in points)
+    pub hidpi_scale: f32,
+    /// JPEG quality (1-100) for photographs sent to iTerm2
+    pub jpeg_quality: u8,
+    /// Largest image to send, in bytes as written to the terminal (after base64 for iTerm2/kitty)
+    pub max_payload: Option<usize>,
+}
+
+impl Default for Encoding {
+    fn default() -> Encoding {
+        Encoding { hidpi_scale: 1.0, jpeg_quality: DEFAULT_JPEG_QUALITY, max_payload: None }
+    }
+}
+
+/// An image encoded for one protocol and size
+pub struct Payload {
+    pub data: Vec<u8>,
+    /// Pixel size actually encoded (smaller than planned if --max-payload had to give)
+    pub dims: (u32, u32),
+    /// "png", "jpeg", "sixel" or "blocks"
+    pub format: &'static str,
+    /// JPEG quality, if JPEG
+    pub quality: Option<u8>,
+}
+
+impl Renderer {
+    /// Names accepted by `--renderer`
+    pub const NAMES: &'static str = "iterm2|kitty|sixel|blocks";
+
+    pub fn from_name(name: &str) -> Option<Renderer> {
+        match name {
+            "iterm2" => Some(Renderer::Iterm2),
+            "kitty" => Some(Renderer::Kitty),
+            "sixel" => Some(Renderer::Sixel),
+            "blocks" => Some(Renderer::Blocks),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Renderer::Iterm2 => "iterm2",
+            Renderer::Kitty => "kitty",
+            Renderer::Sixel => "sixel",
+            Renderer::Blocks => "blocks",
+        }
+    }
+
+    /// Draw an image at the cursor, `width_chars` cells wide, then move to the next line
+    ///
+    /// The image is resampled to the pixels it will cover (see `pixel_size`) before encoding,
+    /// and the terminal is told the width in cells, so the caller's layout is the only
+    /// scaling decision. Payloads are cached on `img`, so redrawing at the same size is cheap.
+    pub fn display(self, img: &CachedImage, width_chars: u32, cell_px: (u32, u32), encoding: Encoding) -> Result<Arc<Payload>, String> {
+        let payload = self.payload(img, width_chars, cell_px, encoding)?;
+        match self {
+            Renderer::Iterm2 => display_iterm2(&payload.data, width_chars),
+            Renderer::Kitty => kitty::display(&payload.data, width_chars),
+            Renderer::Sixel => {
+                let mut stdout = io::stdout();
+                let _ = stdout.write_all(&payload.data);
+                let _ = stdout.write_all(b"\n");
+            }
+            // Ends with its own newline
+            Renderer::Blocks => {
+                let _ = io::stdout().write_all(&payload.data);
+            }
+        }
+        Ok(payload)
+    }
+
+    /// Encode the payload `display` will need, without drawing anything (for prefetching)
+    pub fn prepare(self, img: &CachedImage, width_chars: u32, cell_px: (u32, u32), encoding: Encoding) -> Result<(), String> {
+        self.payload(img, width_chars, cell_px, encoding).map(|_| ())
+    }
+
+    /// Pixel size an image of `dims` is encoded at for a slot `width_chars` cells wide
+    ///
+    /// iTerm2/kitty get the slot's pixel box times the HiDPI scale, but never more than the
+    /// image has. Sixel pixels map 1:1 to the terminal's; blocks have two pixels per cell,
+    /// with rows rounded up like the layout.
+    fn pixel_size(self, dims: (u32, u32), width_chars: u32, cell_px: (u32, u32), encoding: Encoding) -> (u32, u32) {
+        let aspect = dims.1 as f32 / dims.0.max(1) as f32;
+        match self {
+            Renderer::Iterm2 | Renderer::Kitty => {
+                let box_w = (width_chars * cell_px.0) as f32 * encoding.hidpi_scale;
+                let width = (box_w.round() as u32).clamp(1, dims.0.max(1));
+                (width, ((width as f32 * aspect).round() as u32).max(1))
+            }
+            Renderer::Sixel => {
+                let width = (width_chars * cell_px.0).max(2);
+                (width, ((width as f32 * aspect) as u32).max(2))
+            }
+            Renderer::Blocks => {
+                let width = width_chars.max(1);
+                let (cell_w, cell_h) = (cell_px.0.max(1), cell_px.1.max(1));
+                let height_px = width as f32 * cell_w as f32 * aspect;
+                let rows = ((height_px / cell_h as f32).ceil() as u32).max(1);
+                (width, rows * 2)
+            }
+        }
+    }
+
+    /// Resample and encode for this protocol, cached on the image per size
+    ///
+    /// Over the --max-payload budget, JPEG quality is lowered first, then the image is
+    /// resampled smaller until the payload fits (see `shrink_step`). iTerm2 and kitty scale
+    /// it back up to the slot; Sixel and blocks draw it smaller inside the slot.
+    fn payload(self, img: &CachedImage, width_chars: u32, cell_px: (u32, u32), encoding: Encoding) -> Result<Arc<Payload>, String> {
+        img.payload((self, width_chars, cell_px), |image| {
+            let mut dims = self.pixel_size(image.dimensions(), width_chars, cell_px, encoding);
+            let mut resized = resize(image, dims);
+            // JPEG for photographs sent to iTerm2; kitty only takes PNG or raw pixels
+            let mut quality = (self == Renderer::Iterm2 && is_photographic(&resized)).then_some(encoding.jpeg_quality);
+            loop {
+                let (data, format) = self.encode(&resized, quality)?;
+                let sent = self.sent_size(data.len());
+                let next = encoding
+                    .max_payload
+                    .filter(|&limit| sent > limit)
+                    .and_then(|limit| shrink_step(dims, quality, sent, limit));
+                let Some((next_dims, next_quality)) = next else {
+                    return Ok(Payload { data, dims, format, quality });
+                };
+                if next_dims != dims {
+                    resized = resize(image, next_dims);
+                }
+                (dims, quality) = (next_dims, next_quality);
+            }
+        })
+    }
+
+    /// Encode pixels already at their final size: JPEG at `quality` if given, else PNG
+    /// (iTerm2/kitty), or the protocol's own format
+    fn encode(self, image: &DynamicImage, quality: Option<u8>) -> Result<(Vec<u8>, &'static str), String> {
+        Ok(match (self, quality) {
+            (Renderer::Iterm2 | Renderer::Kitty, Some(quality)) => (encode_jpeg(image, quality)?, "jpeg"),
+            (Renderer::Iterm2 | Renderer::Kitty, None) => (encode_png(image)?, "png"),
+            (Renderer::Sixel, _) => (sixel::encode(&image.to_rgba8()).into_bytes(), "sixel"),
+            (Renderer::Blocks, _) => (blocks::render(&image.to_rgba8()).into_bytes(), "blocks"),
+        })
+    }
+
+    /// Bytes written to the terminal for a payload of `len` bytes
+    fn sent_size(self, len: usize) -> usize {
+        match self {
+            // Base64: 4 characters for every 3 bytes
+            Renderer::Iterm2 | Renderer::Kitty => len.div_ceil(3) * 4,
+            Renderer::Sixel | Renderer::Blocks => len,
+        }
+    }
+
+    /// Remove images drawn so far, before the screen is cleared and redrawn
+    pub fn clear_images(self) {
+        match self {
+            Renderer::Iterm2 => {} // Inline images are plain cells, cleared with the screen
+            Renderer::Kitty => kitty::delete_placements(),
+            Renderer::Sixel | Renderer::Blocks => {} // Sixel pixels and glyphs are plain cells too
+        }
+    }
+}
+
+/// Next size and JPEG quality to try after an encoding of `dims` came out `sent` bytes,
+/// over `limit`: lower quality down to MIN_BUDGET_JPEG_QUALITY, then fewer pixels
+///
+/// None once the longer side is down to MIN_BUDGET_DIMENSION; that encoding is sent over budget.
+fn shrink_step(dims: (u32, u32), quality: Option<u8>, sent: usize, limit: usize) -> Option<((u32, u32), Option<u8>)> {
+    if let Some(quality) = quality
+        && quality > MIN_BUDGET_JPEG_QUALITY
+    {
+        let lower = quality.saturating_sub(BUDGET_JPEG_QUALITY_STEP).max(MIN_BUDGET_JPEG_QUALITY);
+        return Some((dims, Some(lower)));
+    }
+    let longer = dims.0.max(dims.1);
+    if longer <= MIN_BUDGET_DIMENSION {
+        return None;
+    }
+    // Size goes roughly with pixel count, so scale each side by the square root
+    let ratio = (limit as f32 / sent as f32)
+        .sqrt()
+        .min(0.9)
+        .max(MIN_BUDGET_DIMENSION as f32 / longer as f32);
+    let scaled = |side: u32| ((side as f32 * ratio) as u32).max(1);
+    Some(((scaled(dims.0), scaled(dims.1)), quality))
+}
+
+/// `image` resampled to `dims`, or borrowed if it already has that size
+fn resize(image: &DynamicImage, dims: (u32, u32)) -> Cow<'_, DynamicImage> {
+    if dims == image.dimensions() {
+        Cow::Borrowed(image)
+    } else {
+        Cow::Owned(image.resize_exact(dims.0, dims.1, FilterType::Triangle))
+    }
+}
+
+/// Opaque with many colours: JPEG will be much smaller than PNG and look the same
+///
+/// Anything with transparency, or few colours (screenshots, diagrams, pixel art), stays PNG.
+fn is_photographic(image: &DynamicImage) -> bool {
+    if image.color().has_alpha() && image.pixels().any(|(_, _, p)| p[3] < 255) {
+        return false;
+    }
+    let mut colours = HashSet::new();
+    for (_, _, p) in image.pixels() {
+        colours.insert([p[0], p[1], p[2]]);
+        if colours.len() >= PHOTO_MIN_COLOURS {
+            return true;
+        }
+    }
+    false
+}
+
+fn encode_jpeg(img: &DynamicImage, quality: u8) -> Result<Vec<u8>, String> {
+    let mut jpeg_data = Vec::new();
+    // JPEG has no alpha; photographs are opaque anyway
+    let rgb = DynamicImage::ImageRgb8(img.to_rgb8());
+    JpegEncoder::new_with_quality(&mut jpeg_data, quality)
+        .encode_image(&rgb)
+        .map_err(|e| e.to_string())?;
+    Ok(jpeg_data)
+}
+
+fn encode_png(img: &DynamicImage) -> Result<Vec<u8>, String> {
+    let mut png_data = Vec::new();
+    let mut cursor = Cursor::new(&mut png_data);
+    img.write_to(&mut cursor, image::ImageFormat::Png)
+        .map_err(|e| e.to_string())?;
+    Ok(png_data)
+}
+
+fn display_iterm2(data: &[u8], width_chars: u32) {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(data);
+    let size = encoded.len();
+
+    // Pass the display_width to iTerm2 - this tells it how wide to make the image
+    // iTerm2 will scale the image to fit this width and maintain aspect ratio
+    println!("\x1b]1337;File=name=image.png;size={};inline=1;width={}c;base64:{}\x07",
+             size, width_chars, encoded);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage, Rgba, RgbaImage};
+
+    #[test]
+    fn base64_protocols_count_the_encoded_size() {
+        assert_eq!(Renderer::Iterm2.sent_size(300), 400);
+        assert_eq!(Renderer::Kitty.sent_size(301), 404);
+        assert_eq!(Renderer::Sixel.sent_size(301), 301);
+        assert_eq!(Renderer::Blocks.sent_size(0), 0);
+    }
+
+    #[test]
+    fn budget_lowers_jpeg_quality_before_resolution() {
+        let mut step = ((800, 600), Some(85));
+        let mut qualities = Vec::new();
+        while let Some(next @ ((800, 600), Some(quality))) = shrink_step(step.0, step.1, 400_000, 100_000) {
+            qualities.push(quality);
+            step = next;
+        }
+        assert_eq!(qualities, [70, 55, 40]);
+        // Then each side shrinks by sqrt(limit / sent), keeping quality at the floor
+        assert_eq!(shrink_step(step.0, step.1, 400_000, 100_000), Some(((400, 300), Some(40))));
+    }
+
+    #[test]
+    fn budget_shrinks_png_at_least_ten_percent_per_step() {
+        assert_eq!(shrink_step((1000, 500), None, 110_000, 100_000), Some(((900, 450), None)));
+        assert_eq!(shrink_step((1000, 500), None, 1_000_000, 10_000), Some(((100, 50), None)));
+    }
+
+    #[test]
+    fn budget_stops_at_the_minimum_dimension() {
+        assert_eq!(shrink_step((1000, 500), None, 4_000_000, 10), Some(((64, 32), None)));
+        assert_eq!(shrink_step((64, 40), None, 1_000_000, 1), None);
+        assert_eq!(shrink_step((64, 40), Some(40), 1_000_000, 1), None);
+        assert!(shrink_step((65, 40), None, 1_000_000, 1).is_some());
+    }
+
+    #[test]
+    fn jpeg_quality_below_the_floor_goes_straight_to_resolution() {
+        assert_eq!(shrink_step((200, 100), Some(30), 200, 100), Some(((141, 70), Some(30))));
+    }
+
+    /// Every pixel a different colour, like a photograph's sensor noise
+    fn noise(width: u32, height: u32) -> RgbImage {
+        RgbImage::from_fn(width, height, |x, y| Rgb([(x * 7) as u8, (y * 13) as u8, (x * y) as u8]))
+    }
+
+    #[test]
+    fn many_opaque_colours_count_as_photographic() {
+        assert!(is_photographic(&DynamicImage::ImageRgb8(noise(64, 64))));
+        let opaque = DynamicImage::ImageRgb8(noise(64, 64)).to_rgba8();
+        assert!(is_photographic(&DynamicImage::ImageRgba8(opaque)));
+    }
+
+    #[test]
+    fn few_colours_or_transparency_stay_png() {
+        let flat = RgbImage::from_fn(64, 64, |x, _| Rgb([(x % 4) as u8 * 60, 0, 0]));
+        assert!(!is_photographic(&DynamicImage::ImageRgb8(flat)));
+
+        let mut translucent: RgbaImage = DynamicImage::ImageRgb8(noise(64, 64)).to_rgba8();
+        translucent.put_pixel(0, 0, Rgba([0, 0, 0, 254]));
+        assert!(!is_photographic(&DynamicImage::ImageRgba8(translucent)));
+    }
+}
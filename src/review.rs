@@ -0,0 +1,148 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::input::{self, Key};
+use crate::journal::{Fingerprint, Journal};
+use crate::render::Renderer;
+use crate::{platform, record_decision, term, Action, Decision};
+
+// With --defer, 'b' only marks an image. This screen lists the marks, lets the
+// user unmark any, then trashes the rest in one go. Unmarked images count as kept.
+
+/// Rows used by the header and footer around the list
+const CHROME_ROWS: usize = 5;
+
+/// How the review screen was left
+pub enum Outcome {
+    /// Marks were applied (or there were none)
+    Applied,
+    /// Esc: nothing changed, marks are still pending
+    Cancelled,
+}
+
+/// Show every pending mark, let the user toggle them, and apply on Enter
+///
+/// Applied decisions are rewritten in `history` (Marked becomes Trash or Keep) and
+/// journaled, so undo and --resume see what actually happened.
+pub fn review_marked(history: &mut [Decision], journal: &mut Option<Journal>, renderer: Renderer) -> Outcome {
+    let marked: Vec<usize> = (0..history.len())
+        .filter(|&i| matches!(history[i].action, Action::Marked))
+        .collect();
+    if marked.is_empty() {
+        return Outcome::Applied;
+    }
+    let sizes: Vec<u64> = marked
+        .iter()
+        .map(|&i| fs::metadata(&history[i].path).map(|m| m.len()).unwrap_or(0))
+        .collect();
+    let mut selected = vec![true; marked.len()];
+    let mut cursor = 0;
+
+    renderer.clear_images();
+    loop {
+        draw(history, &marked, &sizes, &selected, cursor);
+        let key = match input::read_key() {
+            Ok(key) => key,
+            // Resize: the next draw picks up the new size
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => return Outcome::Cancelled,
+        };
+        match key {
+            Key::Up | Key::Char('k') => cursor = cursor.saturating_sub(1),
+            Key::Down | Key::Char('j') => cursor = (cursor + 1).min(marked.len() - 1),
+            Key::Home => cursor = 0,
+            Key::End => cursor = marked.len() - 1,
+            Key::Char(' ' | 'x') => selected[cursor] = !selected[cursor],
+            Key::Char('a') => {
+                // Select all, or none if all are already selected
+                let all = selected.iter().all(|&s| s);
+                selected.iter_mut().for_each(|s| *s = !all);
+            }
+            Key::Enter => break,
+            Key::Esc => {
+                print!("\x1b[2J\x1b[H");
+                return Outcome::Cancelled;
+            }
+            _ => {
+                print!("\x07"); // Bell on invalid input
+                io::stdout().flush().unwrap();
+            }
+        }
+    }
+
+    // Apply, reporting each file as we go
+    print!("\x1b[2J\x1b[H");
+    println!("🗑️  Applying marks:\n");
+    let (mut trashed, mut failed, mut freed) = (0, 0, 0);
+    for (n, &i) in marked.iter().enumerate() {
+        let decision = &mut history[i];
+        let abbrev = term::abbreviate_path(&decision.path, "", 60);
+        if !selected[n] {
+            record_decision(journal, Fingerprint::of(&decision.path), "keep");
+            decision.action = Action::Keep;
+            println!("  ↩️  kept        {}", abbrev);
+            continue;
+        }
+        // Fingerprint first: the file is gone from its path once trashed
+        let fingerprint = Fingerprint::of(&decision.path);
+        match platform::move_to_trash(&decision.path) {
+            Some(location) => {
+                record_decision(journal, fingerprint, "trash");
+                decision.action = Action::Trash(location);
+                trashed += 1;
+                freed += sizes[n];
+                println!("  ✅ trashed     {}", abbrev);
+            }
+            None => {
+                // Left marked so a later review can retry it
+                failed += 1;
+                println!("  ❌ failed      {}", abbrev);
+            }
+        }
+    }
+    println!(
+        "\n{} trashed ({}), {} failed, {} kept",
+        trashed,
+        term::format_bytes(freed),
+        failed,
+        selected.iter().filter(|&&s| !s).count()
+    );
+    println!("(press any key to continue)");
+    io::stdout().flush().unwrap();
+    let _ = input::read_key();
+    print!("\x1b[2J\x1b[H");
+    Outcome::Applied
+}
+
+/// Redraw the whole list, scrolled so the cursor is visible
+fn draw(history: &[Decision], marked: &[usize], sizes: &[u64], selected: &[bool], cursor: usize) {
+    let (cols, rows) = term::get_terminal_size();
+    let visible = (rows as usize).saturating_sub(CHROME_ROWS).max(1);
+    let first = cursor.saturating_sub(visible - 1);
+    let total: u64 = sizes.iter().zip(selected).filter(|(_, s)| **s).map(|(size, _)| size).sum();
+    let count = selected.iter().filter(|&&s| s).count();
+
+    let mut out = String::from("\x1b[2J\x1b[H");
+    out.push_str(&format!(
+        "🗑️  Marked for the trash: {} of {} selected, {}\n\n",
+        count,
+        marked.len(),
+        term::format_bytes(total)
+    ));
+    for n in first..(first + visible).min(marked.len()) {
+        let check = if selected[n] { "[x]" } else { "[ ]" };
+        let size = format!("{:>9}", term::format_bytes(sizes[n]));
+        let path: &Path = &history[marked[n]].path;
+        let abbrev = term::abbreviate_path(path, "", (cols as usize).saturating_sub(18));
+        if n == cursor {
+            // Current: reverse video
+            out.push_str(&format!("\x1b[7m{} {}  {}\x1b[0m\n", check, size, abbrev));
+        } else {
+            out.push_str(&format!("{} {}  {}\n", check, size, abbrev));
+        }
+    }
+    out.push_str("\n\x1b[2m↑/↓ move, space toggle, a all/none, enter apply, esc back\x1b[0m");
+    print!("{}", out);
+    io::stdout().flush().unwrap();
+}
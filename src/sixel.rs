@@ -0,0 +1,164 @@
+use image::imageops;
+use image::RgbaImage;
+use std::fmt::Write as _;
+
+// Sixel: DCS P1;P2;P3 q <raster attrs> <palette> <bands> ST
+// Each band covers 6 pixel rows; each sixel char ('?' + bits) sets one column of a band
+// for the currently selected colour. '$' returns to the band start, '-' moves to the next band.
+
+/// Palette size (the common maximum supported by sixel terminals)
+const PALETTE_SIZE: usize = 256;
+
+/// NeuQuant sampling factor: 1 is best quality, 30 is fastest
+const QUANT_SAMPLE_FACTOR: i32 = 10;
+
+/// Pixels with alpha below this are left transparent
+const ALPHA_THRESHOLD: u8 = 128;
+
+/// Quantize, dither, and encode an RGBA image as a complete Sixel escape sequence
+///
+/// Sixel has no notion of cells, so unlike iTerm2/kitty the image must already be
+/// sized to its pixel box on screen (see `Renderer::pixel_size`).
+pub fn encode(rgba: &RgbaImage) -> String {
+    let (width, height) = rgba.dimensions();
+
+    // Quantize opaque colours only; transparency is handled by P2=1 (untouched pixels stay background)
+    let transparent: Vec<bool> = rgba.pixels().map(|p| p[3] < ALPHA_THRESHOLD).collect();
+    let mut opaque = rgba.clone();
+    for p in opaque.pixels_mut() {
+        p[3] = 255;
+    }
+    let quant = color_quant::NeuQuant::new(QUANT_SAMPLE_FACTOR, PALETTE_SIZE, opaque.as_raw());
+    imageops::dither(&mut opaque, &quant);
+    let indices = imageops::index_colors(&opaque, &quant);
+
+    let mut out = String::new();
+    // P2=1: pixels with no colour set remain the terminal background
+    // "1;1;W;H: 1:1 pixel aspect ratio and raster size
+    let _ = write!(out, "\x1bP0;1;0q\"1;1;{};{}", width, height);
+
+    // Palette in RGB percentages
+    let palette = quant.color_map_rgb();
+    for (idx, rgb) in palette.chunks(3).enumerate() {
+        let pct = |c: u8| c as u32 * 100 / 255;
+        let _ = write!(out, "#{};2;{};{};{}", idx, pct(rgb[0]), pct(rgb[1]), pct(rgb[2]));
+    }
+
+    push_bands(&mut out, indices.as_raw(), &transparent, width, height);
+    out.push_str("\x1b\\");
+    out
+}
+
+/// Append the pixel data: per band of 6 rows, each colour's columns, separated by '$'
+/// within a band and '-' between bands. Transparent pixels are never set.
+fn push_bands(out: &mut String, indices: &[u8], transparent: &[bool], width: u32, height: u32) {
+    // Per-band column bitmasks, only allocated for colours that appear in the band
+    let mut band: Vec<Option<Vec<u8>>> = vec![None; PALETTE_SIZE];
+    for band_top in (0..height).step_by(6) {
+        for y in band_top..(band_top + 6).min(height) {
+            let bit = 1u8 << (y - band_top);
+            for x in 0..width {
+                let offset = (y * width + x) as usize;
+                if transparent[offset] {
+                    continue;
+                }
+                let color = indices[offset] as usize;
+                band[color].get_or_insert_with(|| vec![0; width as usize])[x as usize] |= bit;
+            }
+        }
+
+        let mut first = true;
+        for (color, columns) in band.iter_mut().enumerate() {
+            let Some(columns) = columns.take() else {
+                continue;
+            };
+            if !first {
+                out.push('$');
+            }
+            first = false;
+            let _ = write!(out, "#{}", color);
+            push_run_length_encoded(out, &columns);
+        }
+        out.push('-');
+    }
+}
+
+/// Append sixel chars for one colour's columns, compressing runs with `!<count><char>`
+fn push_run_length_encoded(out: &mut String, columns: &[u8]) {
+    let mut iter = columns.iter().peekable();
+    while let Some(&bits) = iter.next() {
+        let mut count = 1;
+        while iter.peek() == Some(&&bits) {
+            iter.next();
+            count += 1;
+        }
+        let ch = (b'?' + bits) as char;
+        if count > 3 {
+            let _ = write!(out, "!{}{}", count, ch);
+        } else {
+            for _ in 0..count {
+                out.push(ch);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    fn run_length_encoded(columns: &[u8]) -> String {
+        let mut out = String::new();
+        push_run_length_encoded(&mut out, columns);
+        out
+    }
+
+    fn bands(indices: &[u8], transparent: &[bool], width: u32, height: u32) -> String {
+        let mut out = String::new();
+        push_bands(&mut out, indices, transparent, width, height);
+        out
+    }
+
+    #[test]
+    fn runs_longer_than_three_are_compressed() {
+        assert_eq!(run_length_encoded(&[0x3f; 3]), "~~~");
+        assert_eq!(run_length_encoded(&[0x3f; 4]), "!4~");
+        assert_eq!(run_length_encoded(&[1, 1, 2, 0, 0, 0, 0, 0]), "@@A!5?");
+    }
+
+    #[test]
+    fn colours_are_separated_by_carriage_return_and_bands_by_newline() {
+        // 2×7: colour 0 on the left, colour 1 on the right; the 7th row starts a second band
+        let indices = [0, 1].repeat(7);
+        assert_eq!(bands(&indices, &[false; 14], 2, 7), "#0~?$#1?~-#0@?$#1?@-");
+    }
+
+    #[test]
+    fn transparent_pixels_are_left_unset() {
+        let indices = [0u8; 6];
+        let transparent = [false, true, true, true, true, false];
+        // Column 0 keeps only row 0 (bit 1 = '@'), column 1 only row 2 (bit 4 = 'C')
+        assert_eq!(bands(&indices, &transparent, 2, 3), "#0@C-");
+        assert_eq!(bands(&indices, &[true, true, false, false, true, true], 1, 6), "#0K-");
+        assert_eq!(bands(&indices, &[true; 6], 2, 3), "-");
+    }
+
+    #[test]
+    fn encoding_is_a_complete_dcs_sequence_with_raster_attributes() {
+        let image = RgbaImage::from_fn(4, 7, |x, _| if x < 2 { Rgba([255, 0, 0, 255]) } else { Rgba([0, 0, 255, 255]) });
+        let sixel = encode(&image);
+        assert!(sixel.starts_with("\x1bP0;1;0q\"1;1;4;7#0;2;"), "{:?}", &sixel[..20]);
+        assert!(sixel.ends_with("-\x1b\\"));
+        // Two bands of pixel data follow the palette
+        let data = &sixel[sixel.rfind(";2;").unwrap()..];
+        assert_eq!(data.matches('-').count(), 2);
+    }
+
+    #[test]
+    fn fully_transparent_images_select_no_colours() {
+        let sixel = encode(&RgbaImage::from_pixel(3, 12, Rgba([10, 20, 30, 0])));
+        let data = &sixel[sixel.rfind(";2;").unwrap()..];
+        assert!(data.ends_with("--\x1b\\"), "{:?}", data);
+    }
+}
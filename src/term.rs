@@ -1,6 +1,9 @@
-use libc::{ioctl, isatty, STDOUT_FILENO, TIOCGWINSZ, tcgetattr, tcsetattr, STDIN_FILENO, TCSANOW, termios, ECHO, ICANON};
+use libc::{ioctl, isatty, STDOUT_FILENO, TIOCGWINSZ, tcgetattr, tcsetattr, STDIN_FILENO, TCSANOW, termios, ECHO, ICANON, VMIN, VTIME};
 use std::path::Path;
-use std::io::{self, Read};
+use std::io::{self, Write};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 #[repr(C)]
 struct WinSize {
@@ -14,33 +17,118 @@ pub fn get_terminal_size() -> (u16, u16) {
     unsafe {
         if isatty(STDOUT_FILENO) == 0 {
             eprintln!("Error: stdout is not a TTY");
+            restore_terminal();
             std::process::exit(1);
         }
 
         let mut ws: WinSize = std::mem::zeroed();
-        let ret = ioctl(STDOUT_FILENO, TIOCGWINSZ as u64, &mut ws as *mut WinSize);
+        let ret = ioctl(STDOUT_FILENO, TIOCGWINSZ, &mut ws as *mut WinSize);
 
         if ret == -1 {
             eprintln!("Error: failed to get terminal size via ioctl");
+            restore_terminal();
             std::process::exit(1);
         }
         (ws.ws_col, ws.ws_row)
     }
 }
 
+/// Cell size assumed when the terminal does not report pixels (typical 1:2 monospace cell)
+pub const ASSUMED_CELL_PX: (u32, u32) = (8, 16);
+
 /// Get pixel dimensions of terminal via TIOCGWINSZ (iTerm2 reports this).
-pub fn get_terminal_pixel_size() -> (u32, u32) {
+/// Returns None if the terminal leaves the pixel fields zero.
+pub fn get_terminal_pixel_size() -> Option<(u32, u32)> {
     unsafe {
         let mut ws: WinSize = std::mem::zeroed();
-        let ret = ioctl(STDOUT_FILENO, TIOCGWINSZ as u64, &mut ws as *mut WinSize);
+        let ret = ioctl(STDOUT_FILENO, TIOCGWINSZ, &mut ws as *mut WinSize);
 
         if ret == -1 || ws.ws_xpixel == 0 || ws.ws_ypixel == 0 {
-            eprintln!("Error: failed to get terminal pixel dimensions via ioctl");
-            eprintln!("This terminal may not support pixel reporting (iTerm2/Wezterm required)");
-            std::process::exit(1);
+            return None;
+        }
+        Some((ws.ws_xpixel as u32, ws.ws_ypixel as u32))
+    }
+}
+
+/// How long to wait for the terminal to answer a query (generous for SSH)
+const QUERY_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Send a query followed by primary device attributes and return the raw replies
+///
+/// Every terminal answers DA1 (`CSI ? ... c`) and answers in order, so the DA1 reply
+/// marks the end of whatever the first query produced. Raw mode must be enabled.
+pub fn query_terminal(request: &str) -> Vec<u8> {
+    let mut response = Vec::new();
+    print!("{}\x1b[c", request);
+    if io::stdout().flush().is_err() {
+        return response;
+    }
+
+    let deadline = Instant::now() + QUERY_TIMEOUT;
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        let Some(byte) = read_byte_timeout(remaining.as_millis() as i32) else {
+            break;
+        };
+        response.push(byte);
+        if byte == b'c' && parse_device_attributes(&response).is_some() {
+            break;
+        }
+    }
+
+    // Drop anything that arrives late so it is not taken as a keypress
+    flush_input();
+    response
+}
+
+/// Parse `ESC [ ? Pn ; Pn ... c` once the full DA1 reply is in the buffer
+pub fn parse_device_attributes(buf: &[u8]) -> Option<Vec<u32>> {
+    let start = buf.windows(3).position(|w| w == b"\x1b[?")?;
+    let rest = &buf[start + 3..];
+    let end = rest.iter().position(|&b| b == b'c')?;
+    let params = std::str::from_utf8(&rest[..end]).ok()?;
+    Some(params.split(';').filter_map(|p| p.parse().ok()).collect())
+}
+
+/// Ask the terminal for its cell size in pixels via XTWINOPS (raw mode must be enabled)
+///
+/// Used when TIOCGWINSZ leaves the pixel fields zero (tmux, many SSH setups).
+/// Prefers `CSI 16 t` (cell size); otherwise divides `CSI 14 t` (text area size)
+/// by the character grid.
+pub fn query_cell_pixel_size(cols: u16, rows: u16) -> Option<(u32, u32)> {
+    parse_cell_pixel_size(&query_terminal("\x1b[16t\x1b[14t"), cols, rows)
+}
+
+/// Cell size from XTWINOPS replies: CSI 6 ; height ; width t (cell), else
+/// CSI 4 ; height ; width t (text area) divided by the `cols` × `rows` grid
+fn parse_cell_pixel_size(response: &[u8], cols: u16, rows: u16) -> Option<(u32, u32)> {
+    let mut cell = None;
+    let mut area = None;
+    for reply in response.split(|&b| b == 0x1b) {
+        let Some(params) = reply.strip_prefix(b"[").and_then(|r| r.strip_suffix(b"t")) else {
+            continue;
+        };
+        let params: Vec<u32> = String::from_utf8_lossy(params)
+            .split(';')
+            .filter_map(|p| p.parse().ok())
+            .collect();
+        if let [kind, height, width] = params[..] {
+            if height == 0 || width == 0 {
+                continue;
+            }
+            match kind {
+                6 => cell = Some((width, height)),
+                4 => area = Some((width, height)),
+                _ => {}
+            }
         }
-        (ws.ws_xpixel as u32, ws.ws_ypixel as u32)
     }
+
+    cell.or_else(|| {
+        let (width, height) = area?;
+        let cell_w = width / cols.max(1) as u32;
+        let cell_h = height / rows.max(1) as u32;
+        (cell_w > 0 && cell_h > 0).then_some((cell_w, cell_h))
+    })
 }
 
 /// Abbreviate path to fit terminal width, showing relative path
@@ -52,30 +140,67 @@ pub fn abbreviate_path(path: &Path, base_path: &str, max_width: usize) -> String
         .to_string_lossy();
 
     let path_str = rel_path.to_string();
-    
+    // Measure and cut in chars, so multi-byte names are never split mid-character
+    let chars: Vec<char> = path_str.chars().collect();
+
     // If it fits, return as-is
-    if path_str.len() <= max_width {
+    if chars.len() <= max_width {
         return path_str;
     }
 
     // Ellipsize: show start and end with ... in middle
     let ellipsis = "...";
     let avail = max_width.saturating_sub(ellipsis.len());
-    let start_len = (avail + 1) / 2;
+    let start_len = avail.div_ceil(2);
     let end_len = avail / 2;
 
-    let start = &path_str[..start_len.min(path_str.len())];
-    let end = if path_str.len() > start_len {
-        &path_str[path_str.len() - end_len..]
-    } else {
-        ""
-    };
+    let start: String = chars[..start_len].iter().collect();
+    let end: String = chars[chars.len() - end_len..].iter().collect();
 
     format!("{}{}{}", start, ellipsis, end)
 }
 
+/// Human-readable size, e.g. "4.2 MB"
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Local date and time, e.g. "2024-06-01 14:03:27"; None if it can't be converted
+pub fn format_local_time(time: SystemTime) -> Option<String> {
+    let secs = match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => since.as_secs() as libc::time_t,
+        Err(before) => -(before.duration().as_secs() as libc::time_t),
+    };
+    unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&secs, &mut tm).is_null() {
+            return None;
+        }
+        Some(format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            tm.tm_year + 1900,
+            tm.tm_mon + 1,
+            tm.tm_mday,
+            tm.tm_hour,
+            tm.tm_min,
+            tm.tm_sec
+        ))
+    }
+}
+
 /// Enable raw mode (no echo, no canonical mode) and return original termios for restoration
-pub fn enable_raw_mode() -> Result<termios, io::Error> {
+fn enable_raw_mode() -> Result<termios, io::Error> {
     unsafe {
         let mut original: termios = std::mem::zeroed();
         if tcgetattr(STDIN_FILENO, &mut original) != 0 {
@@ -84,8 +209,9 @@ pub fn enable_raw_mode() -> Result<termios, io::Error> {
 
         let mut raw = original;
         raw.c_lflag &= !(ECHO | ICANON);
-        raw.c_cc[6] = 0; // VMIN = 0
-        raw.c_cc[5] = 0; // VTIME = 0
+        // Block until at least one byte is available (indices differ between macOS and Linux)
+        raw.c_cc[VMIN] = 1;
+        raw.c_cc[VTIME] = 0;
 
         if tcsetattr(STDIN_FILENO, TCSANOW, &raw) != 0 {
             return Err(io::Error::last_os_error());
@@ -95,21 +221,226 @@ pub fn enable_raw_mode() -> Result<termios, io::Error> {
     }
 }
 
-/// Restore original termios
-pub fn disable_raw_mode(original: &termios) -> Result<(), io::Error> {
+/// Termios from before raw mode, kept globally so signal handlers and the panic hook can restore it
+static SAVED_TERMIOS: OnceLock<termios> = OnceLock::new();
+
+/// Raw mode for the lifetime of the guard; dropping it (including during a panic) restores the terminal
+pub struct RawModeGuard;
+
+impl RawModeGuard {
+    pub fn enable() -> Result<RawModeGuard, io::Error> {
+        let original = enable_raw_mode()?;
+        let _ = SAVED_TERMIOS.set(original);
+
+        // Bracketed paste, so pasted text arrives as one Key::Paste instead of keystrokes
+        print!("\x1b[?2004h");
+        io::stdout().flush()?;
+        Ok(RawModeGuard)
+    }
+}
+
+impl Drop for RawModeGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
+/// Put the terminal back the way we found it: reset colours, show the cursor,
+/// end bracketed paste, restore termios
+///
+/// Only uses async-signal-safe calls (write, tcsetattr) so signal handlers can call it.
+fn restore_terminal() {
+    // SGR reset in case we were interrupted mid-image (half blocks)
+    let reset = b"\x1b[0m\x1b[?25h\x1b[?2004l";
     unsafe {
-        if tcsetattr(STDIN_FILENO, TCSANOW, original) != 0 {
-            return Err(io::Error::last_os_error());
+        libc::write(STDOUT_FILENO, reset.as_ptr() as *const libc::c_void, reset.len());
+        if let Some(original) = SAVED_TERMIOS.get() {
+            tcsetattr(STDIN_FILENO, TCSANOW, original);
         }
-        Ok(())
     }
 }
 
-/// Read a single character without echo
-pub fn read_single_char() -> Result<char, io::Error> {
-    let mut buf = [0u8; 1];
-    io::stdin().read_exact(&mut buf)?;
-    Ok(buf[0] as char)
+extern "C" fn on_exit_signal(signal: libc::c_int) {
+    restore_terminal();
+    // Re-raise with the default action so the exit status still reports the signal
+    unsafe {
+        libc::signal(signal, libc::SIG_DFL);
+        libc::raise(signal);
+    }
 }
 
+/// Restore the terminal on Ctrl+C, SIGTERM, SIGHUP, SIGQUIT and panics
+///
+/// `process::exit` and fatal signals skip destructors, so the guard alone is not enough.
+pub fn install_exit_handlers() {
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = on_exit_signal as *const () as libc::sighandler_t;
+        libc::sigemptyset(&mut action.sa_mask);
+        for signal in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP, libc::SIGQUIT] {
+            libc::sigaction(signal, &action, std::ptr::null_mut());
+        }
+    }
+
+    // Restore before the panic message is printed so it is readable. Only the main
+    // thread's panics end the program; a background worker's must neither leave it
+    // running out of raw mode nor print over the images. Its batch is decoded again
+    // on the main thread, which reports the failure if it happens again.
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if std::thread::current().name() == Some("main") {
+            restore_terminal();
+            default_hook(info);
+        }
+    }));
+}
+
+/// Read one byte if it arrives within `timeout_ms` (for terminal query responses)
+pub fn read_byte_timeout(timeout_ms: i32) -> Option<u8> {
+    let mut fds = libc::pollfd { fd: STDIN_FILENO, events: libc::POLLIN, revents: 0 };
+    let ready = unsafe { libc::poll(&mut fds, 1, timeout_ms) };
+    if ready <= 0 {
+        return None;
+    }
+    // Unbuffered read: std's Stdin buffer would hide pending bytes from poll()
+    let mut byte = 0u8;
+    let n = unsafe { libc::read(STDIN_FILENO, &mut byte as *mut u8 as *mut libc::c_void, 1) };
+    if n == 1 { Some(byte) } else { None }
+}
+
+/// Discard any pending input (e.g. late responses to terminal queries)
+pub fn flush_input() {
+    unsafe {
+        libc::tcflush(STDIN_FILENO, libc::TCIFLUSH);
+    }
+}
+
+/// Set by the SIGWINCH handler, cleared by `take_resized`
+static RESIZED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_sigwinch(_signal: libc::c_int) {
+    RESIZED.store(true, Ordering::SeqCst);
+}
+
+/// Watch for terminal resizes
+///
+/// Installed without SA_RESTART so a blocked `read_byte` returns
+/// `ErrorKind::Interrupted` and the caller can re-layout immediately.
+pub fn install_resize_handler() {
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = on_sigwinch as *const () as libc::sighandler_t;
+        action.sa_flags = 0;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(libc::SIGWINCH, &action, std::ptr::null_mut());
+    }
+}
+
+/// Run `spawn` (which starts a background worker) with SIGWINCH blocked
+///
+/// The kernel delivers a process signal to any thread that doesn't block it; if a
+/// worker took it, the main thread's `read_byte` would not be interrupted. New threads
+/// inherit the mask, so blocking it around the spawn leaves no window for that. A resize
+/// meanwhile stays pending and reaches this thread once the mask is restored.
+pub fn without_resize_signal<T>(spawn: impl FnOnce() -> T) -> T {
+    unsafe {
+        let mut set: libc::sigset_t = std::mem::zeroed();
+        let mut previous: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut set);
+        libc::sigaddset(&mut set, libc::SIGWINCH);
+        libc::pthread_sigmask(libc::SIG_BLOCK, &set, &mut previous);
+        let result = spawn();
+        libc::pthread_sigmask(libc::SIG_SETMASK, &previous, std::ptr::null_mut());
+        result
+    }
+}
+
+/// True (once) if the terminal was resized since the last call
+pub fn take_resized() -> bool {
+    RESIZED.swap(false, Ordering::SeqCst)
+}
+
+/// Read a single byte without echo (see `input::read_key` for whole keypresses)
+///
+/// Unbuffered so pending bytes stay visible to poll(); returns
+/// `ErrorKind::Interrupted` when a signal (e.g. SIGWINCH) arrives while waiting.
+pub fn read_byte() -> Result<u8, io::Error> {
+    let mut byte = 0u8;
+    let n = unsafe { libc::read(STDIN_FILENO, &mut byte as *mut u8 as *mut libc::c_void, 1) };
+    match n {
+        1 => Ok(byte),
+        0 => Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn device_attributes_list_every_parameter() {
+        assert_eq!(parse_device_attributes(b"\x1b[?62;4;22c"), Some(vec![62, 4, 22]));
+        assert_eq!(parse_device_attributes(b"\x1b[?1;2c"), Some(vec![1, 2]));
+    }
+
+    #[test]
+    fn sixel_shows_up_as_attribute_4_only() {
+        let with = parse_device_attributes(b"\x1b[?64;1;4;6;9;15;22c").unwrap();
+        assert!(with.contains(&4));
+        let without = parse_device_attributes(b"\x1b[?64;1;6;9;15;22;44c").unwrap();
+        assert!(!without.contains(&4));
+    }
 
+    #[test]
+    fn device_attributes_follow_other_replies() {
+        let reply = b"\x1b_Gi=31;OK\x1b\\\x1b[?62;4c";
+        assert_eq!(parse_device_attributes(reply), Some(vec![62, 4]));
+    }
+
+    #[test]
+    fn cell_size_reply_wins() {
+        assert_eq!(parse_cell_pixel_size(b"\x1b[6;16;8t", 80, 24), Some((8, 16)));
+        assert_eq!(parse_cell_pixel_size(b"\x1b[4;480;800t\x1b[6;20;10t", 80, 24), Some((10, 20)));
+    }
+
+    #[test]
+    fn text_area_is_divided_by_the_grid() {
+        assert_eq!(parse_cell_pixel_size(b"\x1b[4;384;640t", 80, 24), Some((8, 16)));
+        // Remainders are dropped
+        assert_eq!(parse_cell_pixel_size(b"\x1b[4;390;645t", 80, 24), Some((8, 16)));
+    }
+
+    #[test]
+    fn zero_sizes_are_ignored() {
+        assert_eq!(parse_cell_pixel_size(b"\x1b[6;0;0t", 80, 24), None);
+        assert_eq!(parse_cell_pixel_size(b"\x1b[6;0;0t\x1b[4;384;640t", 80, 24), Some((8, 16)));
+        // An area smaller than the grid would make a zero-pixel cell
+        assert_eq!(parse_cell_pixel_size(b"\x1b[4;10;40t", 80, 24), None);
+    }
+
+    #[test]
+    fn cell_replies_arrive_with_device_attributes() {
+        let reply = b"\x1b[6;17;9t\x1b[4;408;720t\x1b[?62;4;22c";
+        assert_eq!(parse_cell_pixel_size(reply, 80, 24), Some((9, 17)));
+        // Terminals without XTWINOPS answer only DA1
+        assert_eq!(parse_cell_pixel_size(b"\x1b[?62;22c", 80, 24), None);
+    }
+
+    #[test]
+    fn incomplete_device_attributes_are_not_parsed() {
+        assert_eq!(parse_device_attributes(b""), None);
+        assert_eq!(parse_device_attributes(b"\x1b[?62;4"), None);
+        assert_eq!(parse_device_attributes(b"\x1b[6;16;8t"), None);
+    }
+
+    #[test]
+    fn long_paths_are_cut_on_character_boundaries() {
+        let path = Path::new("/home/me/写真/旅行/京都の写真_0001.jpg");
+        assert_eq!(abbreviate_path(path, "", 22), "/home/me/写..._0001.jpg");
+        assert_eq!(abbreviate_path(path, "", 24), "/home/me/写真...真_0001.jpg");
+        assert_eq!(abbreviate_path(path, "", 26), "/home/me/写真/...写真_0001.jpg");
+        assert_eq!(abbreviate_path(path, "", 30), path.to_str().unwrap());
+        assert_eq!(abbreviate_path(path, "/home/me", 30), "写真/旅行/京都の写真_0001.jpg");
+    }
+}
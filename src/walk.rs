@@ -0,0 +1,85 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::formats;
+
+/// System/trash directories never descended into
+pub const SKIP_DIRS: [&str; 4] = [".Trash", ".Volumes", ".TemporaryItems", ".DS_Store"];
+
+/// True if the file extension belongs to a format this build decodes (case-insensitive)
+pub fn has_image_extension(path: &Path) -> bool {
+    formats::for_path(path).is_some()
+}
+
+/// True if any component of the path is in SKIP_DIRS
+pub fn is_skipped(path: &Path) -> bool {
+    path.components().any(|component| {
+        component.as_os_str().to_str().is_some_and(|name| SKIP_DIRS.contains(&name))
+    })
+}
+
+/// "photo.jpg", "photo.2.jpg", "photo.3.jpg", ...
+pub fn collision_name(file_name: &str, n: u32) -> String {
+    if n == 1 {
+        return file_name.to_string();
+    }
+    match file_name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{}.{}.{}", stem, n, ext),
+        _ => format!("{}.{}", file_name, n),
+    }
+}
+
+/// Find images with a pure-Rust directory walk (same semantics as the macOS enumerator)
+///
+/// Entries directly inside `path` are at depth 1; anything deeper than `max_depth` is
+/// skipped without descending. Symlinked directories are not followed.
+#[cfg_attr(target_os = "macos", allow(dead_code))]
+pub fn find_images(path: &str, max_depth: usize) -> Vec<PathBuf> {
+    let mut images = Vec::new();
+    walk_dir(Path::new(path), 1, max_depth, &mut images);
+    images
+}
+
+fn walk_dir(dir: &Path, depth: usize, max_depth: usize, images: &mut Vec<PathBuf>) {
+    if depth > max_depth {
+        return;
+    }
+
+    // Unreadable directories are silently skipped, like the enumerator with no error handler
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let entry_path = entry.path();
+        if is_skipped(&entry_path) {
+            continue;
+        }
+
+        // file_type() does not follow symlinks, so symlinked dirs are treated as leaves
+        let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+        if is_dir {
+            walk_dir(&entry_path, depth + 1, max_depth, images);
+        } else if has_image_extension(&entry_path) {
+            images.push(entry_path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collision_name_counts_before_the_extension() {
+        assert_eq!(collision_name("photo.jpg", 1), "photo.jpg");
+        assert_eq!(collision_name("photo.jpg", 2), "photo.2.jpg");
+        assert_eq!(collision_name("archive.tar.gz", 3), "archive.tar.3.gz");
+    }
+
+    #[test]
+    fn collision_name_appends_when_there_is_no_extension() {
+        assert_eq!(collision_name("README", 2), "README.2");
+        assert_eq!(collision_name(".hidden", 2), ".hidden.2");
+    }
+}
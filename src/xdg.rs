@@ -0,0 +1,254 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+// Discovery is plain filesystem walking on non-macOS systems
+pub use crate::walk::find_images;
+use crate::destinations::copy_contents;
+use crate::walk::collision_name;
+
+/// Move a file to the trash following the freedesktop.org Trash specification,
+/// returning where it ended up so it can be restored
+pub fn move_to_trash(path: &Path) -> Option<PathBuf> {
+    match trash_file(path) {
+        Ok(trashed) => Some(trashed),
+        Err(e) => {
+            eprintln!("\nTrash error: {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Move a trashed file back to its original path and drop its `.trashinfo`
+///
+/// Refuses to overwrite if something now exists at the original path.
+pub fn restore_from_trash(trashed: &Path, original: &Path) -> bool {
+    if original.symlink_metadata().is_ok() {
+        eprintln!("\nCannot restore, file exists: {}", original.display());
+        return false;
+    }
+    if let Err(e) = move_file(trashed, original) {
+        eprintln!("\nRestore error: {}: {}", original.display(), e);
+        return false;
+    }
+
+    // <trash>/files/<name> has its metadata in <trash>/info/<name>.trashinfo
+    if let (Some(trash_dir), Some(name)) = (trashed.parent().and_then(Path::parent), trashed.file_name()) {
+        let info_file = trash_dir
+            .join("info")
+            .join(format!("{}.trashinfo", name.to_string_lossy()));
+        let _ = fs::remove_file(info_file);
+    }
+    true
+}
+
+/// Trash a file, returning where it ended up inside `<trash>/files`
+///
+/// Files on the home trash's filesystem go to `$XDG_DATA_HOME/Trash`; files on other
+/// mounts go to `$topdir/.Trash/$uid` (if an admin created a sticky `.Trash`) or
+/// `$topdir/.Trash-$uid`, so the move is usually a cheap same-device rename. If neither
+/// can be created there (read-only or foreign mount root), the file is copied into the
+/// home trash instead, as the spec allows.
+fn trash_file(path: &Path) -> io::Result<PathBuf> {
+    let path = std::path::absolute(path)?;
+    let file_dev = fs::symlink_metadata(&path)?.dev();
+
+    let home_trash = home_trash_dir()?;
+    let (trash_dir, info_path) = if device_of_existing_ancestor(&home_trash)? == file_dev {
+        // Home trash stores absolute paths
+        (home_trash, path.clone())
+    } else {
+        // Top directory trashes store paths relative to the mount point
+        let topdir = mount_point(&path, file_dev)?;
+        match topdir_trash_dir(&topdir) {
+            Ok(trash_dir) => {
+                let relative = path.strip_prefix(&topdir).unwrap_or(&path).to_path_buf();
+                (trash_dir, relative)
+            }
+            Err(_) => (home_trash, path.clone()),
+        }
+    };
+
+    let files_dir = trash_dir.join("files");
+    let info_dir = trash_dir.join("info");
+    create_private_dir(&files_dir)?;
+    create_private_dir(&info_dir)?;
+
+    // Reserve a unique name by atomically creating the .trashinfo file first
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?
+        .to_string_lossy()
+        .into_owned();
+    let contents = format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        percent_encode(&info_path),
+        deletion_date()
+    );
+
+    for n in 1.. {
+        let name = collision_name(&file_name, n);
+        let info_file = info_dir.join(format!("{}.trashinfo", name));
+        let mut info = match OpenOptions::new().write(true).create_new(true).open(&info_file) {
+            Ok(f) => f,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        };
+
+        let dest = files_dir.join(&name);
+        if dest.symlink_metadata().is_ok() {
+            // Orphaned file without info: leave it alone and try the next name
+            let _ = fs::remove_file(&info_file);
+            continue;
+        }
+
+        let result = info
+            .write_all(contents.as_bytes())
+            .and_then(|_| info.sync_all())
+            .and_then(|_| move_file(&path, &dest));
+        if let Err(e) = result {
+            let _ = fs::remove_file(&info_file);
+            return Err(e);
+        }
+        return Ok(dest);
+    }
+    unreachable!()
+}
+
+/// `$XDG_DATA_HOME/Trash`, defaulting to `~/.local/share/Trash`
+fn home_trash_dir() -> io::Result<PathBuf> {
+    if let Some(data_home) = env::var_os("XDG_DATA_HOME").filter(|v| !v.is_empty()) {
+        return Ok(PathBuf::from(data_home).join("Trash"));
+    }
+    let home = env::var_os("HOME")
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "HOME is not set"))?;
+    Ok(PathBuf::from(home).join(".local/share/Trash"))
+}
+
+/// Device of the path, or of its nearest existing ancestor (the trash may not exist yet)
+fn device_of_existing_ancestor(path: &Path) -> io::Result<u64> {
+    let mut current = Some(path);
+    while let Some(p) = current {
+        if let Ok(meta) = fs::metadata(p) {
+            return Ok(meta.dev());
+        }
+        current = p.parent();
+    }
+    Err(io::Error::new(io::ErrorKind::NotFound, "no existing ancestor"))
+}
+
+/// Walk up from the file to the highest directory still on the same device
+fn mount_point(path: &Path, dev: u64) -> io::Result<PathBuf> {
+    let mut topdir = path.parent().unwrap_or(Path::new("/")).to_path_buf();
+    while let Some(parent) = topdir.parent() {
+        if fs::metadata(parent)?.dev() != dev {
+            break;
+        }
+        topdir = parent.to_path_buf();
+    }
+    Ok(topdir)
+}
+
+/// Pick the trash directory for a top directory, per the spec's two methods
+fn topdir_trash_dir(topdir: &Path) -> io::Result<PathBuf> {
+    let uid = unsafe { libc::getuid() };
+
+    // Method 1: admin-provided $topdir/.Trash, must be a real sticky directory
+    let shared = topdir.join(".Trash");
+    if let Ok(meta) = fs::symlink_metadata(&shared) {
+        let sticky = meta.permissions().mode() & 0o1000 != 0;
+        if meta.is_dir() && sticky {
+            let user_dir = shared.join(uid.to_string());
+            if create_private_dir(&user_dir).is_ok() {
+                return Ok(user_dir);
+            }
+        }
+    }
+
+    // Method 2: per-user $topdir/.Trash-$uid
+    let user_dir = topdir.join(format!(".Trash-{}", uid));
+    create_private_dir(&user_dir)?;
+    Ok(user_dir)
+}
+
+/// rename(), or copy then delete when `to` is on another filesystem (home trash fallback)
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.raw_os_error() == Some(libc::EXDEV) => {
+            // Keeps the mtime, so a restored photo still sorts by date and matches its journal entry
+            let file = OpenOptions::new().write(true).create_new(true).open(to)?;
+            copy_contents(from, file)
+                .and_then(|()| fs::remove_file(from))
+                .inspect_err(|_| {
+                    let _ = fs::remove_file(to);
+                })
+        }
+        result => result,
+    }
+}
+
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)
+}
+
+/// URL-escape a path for the `Path=` key (RFC 2396, keeping '/' separators)
+fn percent_encode(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut out = String::new();
+    for &b in path.as_os_str().as_bytes() {
+        if b.is_ascii_alphanumeric() || b"/-_.!~*'()".contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    out
+}
+
+/// Current local time as YYYY-MM-DDThh:mm:ss
+fn deletion_date() -> String {
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&now, &mut tm);
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            tm.tm_year + 1900,
+            tm.tm_mon + 1,
+            tm.tm_mday,
+            tm.tm_hour,
+            tm.tm_min,
+            tm.tm_sec
+        )
+    }
+}
+
+/// Open a preview of a file with the desktop's default viewer
+pub fn quicklook_preview(path: &Path) {
+    use std::process::{Command, Stdio};
+
+    let _ = Command::new("xdg-open")
+        .arg(path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_encode_keeps_separators_and_unreserved_characters() {
+        assert_eq!(percent_encode(Path::new("/home/me/a-b_c.d!~*'().jpg")), "/home/me/a-b_c.d!~*'().jpg");
+    }
+
+    #[test]
+    fn percent_encode_escapes_spaces_reserved_and_non_ascii_bytes() {
+        assert_eq!(percent_encode(Path::new("/tmp/my photo#1%.jpg")), "/tmp/my%20photo%231%25.jpg");
+        assert_eq!(percent_encode(Path::new("Café.png")), "Caf%C3%A9.png");
+    }
+}